@@ -2,7 +2,7 @@ use rand::seq::IndexedRandom;
 use rand::RngExt;
 use ronde_lib::{
     history::{CommandHistory, CommandHistoryEntry, History, HistoryItemError, TimeTag},
-    runner::CommandOutput,
+    runner::{CommandOutput, ResourceUsage},
 };
 use std::vec::Vec;
 
@@ -220,6 +220,13 @@ fn gen_command_history_entry(timestamp: &str) -> CommandHistoryEntry {
         tag: TimeTag::Minute(0),
         result,
         command: generate_random_sentence(),
+        usage: Some(ResourceUsage {
+            user_time: rand::rng().random_range(0_u64..2_000_000_u64),
+            system_time: rand::rng().random_range(0_u64..500_000_u64),
+            max_rss: rand::rng().random_range(1_000_u64..100_000_u64),
+            voluntary_switches: rand::rng().random_range(0_u64..1_000_u64),
+            involuntary_switches: rand::rng().random_range(0_u64..100_u64),
+        }),
     }
 }
 
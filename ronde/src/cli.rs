@@ -0,0 +1,96 @@
+use clap::{ArgAction, Args, Parser, Subcommand};
+use ronde_lib::config::ConfigFormat;
+use ronde_lib::report::OutputFormat;
+
+/// Monitor your servers and services with alerting and a simple status page
+#[derive(Debug, Parser)]
+#[command(version, about)]
+pub struct Cli {
+    /// Format of the config file, guessed from its extension by default
+    #[arg(long, global = true, value_name = "toml|yaml|json")]
+    pub format: Option<ConfigFormat>,
+    /// Log more, can be repeated: -v for info, -vv for debug, -vvv for trace
+    #[arg(short, long, global = true, action = ArgAction::Count)]
+    pub verbose: u8,
+    /// Subcommand to run
+    #[command(subcommand)]
+    pub command: Option<Command>,
+    /// Config file describing the services to monitor, to run them as
+    /// `ronde run` does
+    pub config_file: Option<String>,
+}
+
+/// Subcommands
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Run the commands, update the status page and send notifications
+    Run(RunArgs),
+    /// Validate the configuration without running anything
+    CheckConfig {
+        /// Config file
+        config_file: String,
+    },
+    /// Print the latest state of each command from the history file
+    Status {
+        /// Config file
+        config_file: String,
+    },
+    /// Dump the history entries of a command
+    History {
+        /// Config file
+        config_file: String,
+        /// Name of the command
+        name: String,
+    },
+    /// Send a sample notification through each configured channel
+    TestNotify {
+        /// Config file
+        config_file: String,
+    },
+}
+
+impl Command {
+    /// Get the config file of the subcommand
+    pub fn config_file(&self) -> &str {
+        match self {
+            Command::Run(args) => &args.config_file,
+            Command::CheckConfig { config_file }
+            | Command::Status { config_file }
+            | Command::History { config_file, .. }
+            | Command::TestNotify { config_file } => config_file,
+        }
+    }
+}
+
+/// Arguments of the `run` subcommand
+#[derive(Debug, Args)]
+pub struct RunArgs {
+    /// Config file
+    pub config_file: String,
+    /// Only run the command with this name, can be repeated
+    #[arg(long, value_name = "NAME")]
+    pub only: Vec<String>,
+    /// Only run the commands with this tag, can be repeated
+    #[arg(long, value_name = "TAG")]
+    pub tag: Vec<String>,
+    /// Run the commands but do not save the history, update the status page
+    /// nor send notifications
+    #[arg(long)]
+    pub dry_run: bool,
+    /// Print the results of the run on stdout in this format
+    #[arg(long, value_name = "json|tap|junit")]
+    pub output: Option<OutputFormat>,
+}
+
+impl RunArgs {
+    /// Arguments of `ronde <config_file>`
+    pub fn new(config_file: String) -> Self {
+        RunArgs {
+            config_file,
+            only: Vec::new(),
+            tag: Vec::new(),
+            dry_run: false,
+            output: None,
+        }
+    }
+}
@@ -0,0 +1,134 @@
+use ronde_lib::config::{LogFormat, LogLevel, LoggingConfig};
+use std::io::{self, Write};
+use std::os::unix::net::UnixDatagram;
+use std::sync::Arc;
+use tracing::level_filters::LevelFilter;
+use tracing::{Level, Metadata};
+use tracing_subscriber::fmt::MakeWriter;
+
+/// Path of the socket of the local syslog daemon
+const SYSLOG_SOCKET: &str = "/dev/log";
+
+/// Syslog facility used by ronde: daemon
+const SYSLOG_FACILITY: u8 = 3;
+
+/// Writes each log event as a syslog message
+#[derive(Clone)]
+struct Syslog {
+    /// Socket connected to the syslog daemon
+    socket: Arc<UnixDatagram>,
+}
+
+/// A single syslog message, sent when dropped
+struct SyslogMessage {
+    /// Socket connected to the syslog daemon
+    socket: Arc<UnixDatagram>,
+    /// Message, starting with its header
+    buf: Vec<u8>,
+}
+
+impl Syslog {
+    fn connect() -> io::Result<Self> {
+        let socket = UnixDatagram::unbound()?;
+        socket.connect(SYSLOG_SOCKET)?;
+        Ok(Syslog {
+            socket: Arc::new(socket),
+        })
+    }
+
+    fn message(&self, level: &Level) -> SyslogMessage {
+        let severity = match *level {
+            Level::ERROR => 3,
+            Level::WARN => 4,
+            Level::INFO => 6,
+            Level::DEBUG | Level::TRACE => 7,
+        };
+        let header = format!(
+            "<{}>ronde[{}]: ",
+            SYSLOG_FACILITY * 8 + severity,
+            std::process::id()
+        );
+        SyslogMessage {
+            socket: self.socket.clone(),
+            buf: header.into_bytes(),
+        }
+    }
+}
+
+impl Write for SyslogMessage {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buf.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for SyslogMessage {
+    fn drop(&mut self) {
+        while self.buf.last() == Some(&b'\n') {
+            self.buf.pop();
+        }
+        // Nowhere to report the failure to
+        let _ = self.socket.send(&self.buf);
+    }
+}
+
+impl<'a> MakeWriter<'a> for Syslog {
+    type Writer = SyslogMessage;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        self.message(&Level::INFO)
+    }
+
+    fn make_writer_for(&'a self, meta: &Metadata<'_>) -> Self::Writer {
+        self.message(meta.level())
+    }
+}
+
+/// Get the maximum level of the logs, raised by each `-v` flag
+fn max_level(level: LogLevel, verbose: u8) -> LevelFilter {
+    let levels = [
+        LevelFilter::ERROR,
+        LevelFilter::WARN,
+        LevelFilter::INFO,
+        LevelFilter::DEBUG,
+        LevelFilter::TRACE,
+    ];
+    let base = match level {
+        LogLevel::Error => 0,
+        LogLevel::Warn => 1,
+        LogLevel::Info => 2,
+        LogLevel::Debug => 3,
+        LogLevel::Trace => 4,
+    };
+    levels[(base + verbose as usize).min(levels.len() - 1)]
+}
+
+/// Install the global logger
+///
+/// Falls back to text logs on stderr if the syslog daemon cannot be reached.
+pub fn init(config: &LoggingConfig, verbose: u8) {
+    let max_level = max_level(config.level, verbose);
+    let builder = tracing_subscriber::fmt()
+        .with_max_level(max_level)
+        .with_target(false);
+    match config.format {
+        LogFormat::Text => builder.with_writer(io::stderr).init(),
+        LogFormat::Json => builder.json().with_writer(io::stderr).init(),
+        LogFormat::Syslog => match Syslog::connect() {
+            Ok(syslog) => builder
+                .without_time()
+                .with_ansi(false)
+                .with_level(false)
+                .with_writer(syslog)
+                .init(),
+            Err(e) => {
+                builder.with_writer(io::stderr).init();
+                tracing::warn!(error = %e, "unable to connect to {SYSLOG_SOCKET}, logging to stderr");
+            }
+        },
+    }
+}
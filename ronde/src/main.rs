@@ -1,84 +1,225 @@
+use clap::Parser;
 use futures::future::join_all;
+use std::process::ExitCode;
 
-use ronde_lib::config::Config;
+use ronde_lib::check::check_config;
+use ronde_lib::config::{Config, ConfigFormat, LoggingConfig};
 use ronde_lib::error::RondeError;
 use ronde_lib::history::History;
 use ronde_lib::html;
-use ronde_lib::notification::check_and_send_notifications;
+use ronde_lib::notification::{check_and_send_notifications, send_test_notification};
+use ronde_lib::report::{self, Outcome};
 use ronde_lib::runner;
 use ronde_lib::summary::Summary;
+use ronde_lib::user::drop_privileges;
 
-use libc::setgid;
-use libc::setuid;
-
-/// Display usage
-fn usage() {
-    println!("ronde version {}", env!("CARGO_PKG_VERSION"));
-    println!("Monitor your servers and services with alerting and a simple status page");
-    println!();
-    println!("USAGE:");
-    println!("    ronde <YamlConfigFile>");
-    println!();
-    println!("FLAGS:");
-    println!("    -h, --help       Prints help information");
-    println!();
-    println!("ARGS:");
-    println!("    <YamlConfigFile>    YAML Config file describing the services to monitor");
+use tracing::info;
+
+mod cli;
+mod logging;
+use cli::{Cli, Command, RunArgs};
+
+/// Load the configuration, guessing its format from its extension unless
+/// given
+async fn load_config(
+    config_file: &str,
+    format: Option<ConfigFormat>,
+) -> Result<Config, RondeError> {
+    let format = format.unwrap_or_else(|| ConfigFormat::from_path(config_file));
+    Ok(Config::load_with_format(config_file, format).await?)
 }
 
-#[tokio::main]
-/// Main function
-async fn main() -> Result<(), RondeError> {
-    let args: Vec<String> = std::env::args().collect();
-    if args.len() != 2 {
-        usage();
-        return Err(RondeError::CliError());
-    }
-    if args[1] == "-h" || args[1] == "--help" {
-        usage();
-        return Ok(());
+/// Run the commands, returns the outcome of the run
+async fn run(args: RunArgs, config: Config) -> Result<Outcome, RondeError> {
+    if let Some(name) = args
+        .only
+        .iter()
+        .find(|name| !config.commands.iter().any(|c| &c.name == *name))
+    {
+        return Err(RondeError::UnknownCommand(name.clone()));
     }
-
-    let yaml_file = &args[1];
-    let config = Config::load(yaml_file).await?;
+    let selected = config.commands.iter().filter(|c| {
+        (args.only.is_empty() || args.only.contains(&c.name))
+            && (args.tag.is_empty() || c.tags.iter().any(|t| args.tag.contains(t)))
+    });
 
     let results = join_all(
-        config
-            .commands
-            .into_iter()
+        selected
+            .cloned()
             .map(|c| runner::execute_command(c, &config.default_env)),
     )
     .await;
 
-    /* Stop running as root */
-    if let Some(gid) = config.gid {
-        let result = unsafe { setgid(gid) };
-        if result != 0 {
-            panic!("Failed to setgid to {gid}");
+    let outcome = Outcome::from_results(&results);
+    if let Some(output) = args.output {
+        let summary = Summary::from_results(&results);
+        print!(
+            "{}",
+            report::render(output, &config.name, &results, &summary, &config.secrets)
+        );
+    } else if args.dry_run {
+        for result in results.iter() {
+            match result.result {
+                Ok(_) => println!("ok    {}", result.config.name),
+                Err(ref e) => println!("FAIL  {}: {}", result.config.name, e),
+            }
         }
     }
-    if let Some(uid) = config.uid {
-        let result = unsafe { setuid(uid) };
-        if result != 0 {
-            panic!("Failed to setuid to {uid}");
-        }
+    if args.dry_run {
+        return Ok(outcome);
+    }
+
+    /* Stop running as root */
+    if config.uid.is_some() || config.gid.is_some() {
+        info!(uid = config.uid, gid = config.gid, "dropping privileges");
     }
+    drop_privileges(config.uid, config.gid)?;
 
     let mut history = History::load(&config.history_file).await?;
 
-    history.purge_from_results(&results);
-    let summary = Summary::from_results(&results);
-    history.update(results);
+    history.purge_from_config(&config.commands);
+    history.update(results, &config.secrets);
+    let summary = history.get_summary_from_latest();
+    history.apply_output_retention(config.output_retention);
     history.recreate_tags();
     history.rotate();
 
     html::generate_json_files(&config.output_dir, summary, &history, "Ronde".to_string()).await?;
     html::generate_auxiliary_files(&config.output_dir).await?;
 
-    if let Some(ref nconfig) = config.notifications {
-        check_and_send_notifications(nconfig, &mut history).await?;
-    }
+    let notified = match config.notifications {
+        Some(ref nconfig) => {
+            check_and_send_notifications(nconfig, &config.commands, &mut history).await
+        }
+        None => Ok(()),
+    };
 
+    // Save the history even if a channel failed, so that the working ones
+    // are not notified again
     history.save(&config.history_file).await?;
+    notified?;
+    Ok(outcome)
+}
+
+/// Print the latest state of each command, returns the outcome of their
+/// latest runs
+fn status(config: Config, history: History) -> Outcome {
+    let mut outcome = Outcome::Ok;
+    for command in config.commands.iter() {
+        let entry = history
+            .commands
+            .iter()
+            .find(|c| c.name == command.name)
+            .and_then(|c| c.entries.last());
+        match entry {
+            None => println!("?     {}: never run", command.name),
+            Some(entry) => match entry.result {
+                Ok(_) => println!("ok    {} ({})", command.name, entry.timestamp),
+                Err(ref e) => {
+                    outcome = outcome.max(Outcome::from_severity(command.severity));
+                    println!("FAIL  {} ({}): {}", command.name, entry.timestamp, e);
+                }
+            },
+        }
+    }
+    outcome
+}
+
+/// Dump the history entries of a command as JSON
+fn history(history: History, name: &str) -> Result<(), RondeError> {
+    let command = history
+        .commands
+        .iter()
+        .find(|c| c.name == name)
+        .ok_or_else(|| RondeError::UnknownCommand(name.to_string()))?;
+    let json = serde_json::to_string_pretty(&command.entries)
+        .map_err(ronde_lib::history::HistoryError::from)?;
+    println!("{json}");
     Ok(())
 }
+
+/// Send a sample notification through each configured channel
+async fn test_notify(config: Config) -> Result<(), RondeError> {
+    // Notify with the same identity as `run` does
+    drop_privileges(config.uid, config.gid)?;
+    match config.notifications {
+        Some(ref nconfig) => Ok(send_test_notification(nconfig).await?),
+        None => {
+            eprintln!("No notification configured");
+            Ok(())
+        }
+    }
+}
+
+/// Run a subcommand other than `check-config`
+async fn dispatch(command: Command, config: Config) -> Result<Outcome, RondeError> {
+    match command {
+        Command::Run(args) => run(args, config).await,
+        Command::Status { .. } => {
+            let history = History::load(&config.history_file).await?;
+            Ok(status(config, history))
+        }
+        Command::History { name, .. } => {
+            let history_file = config.history_file;
+            history(History::load(&history_file).await?, &name).map(|_| Outcome::Ok)
+        }
+        Command::TestNotify { .. } => test_notify(config).await.map(|_| Outcome::Ok),
+        Command::CheckConfig { .. } => unreachable!("check-config does not load the config"),
+    }
+}
+
+#[tokio::main]
+/// Main function
+///
+/// Exits with 0 when all commands succeeded, 1 when only commands whose
+/// severity is `warning` failed, 2 when a critical command failed and 3 on
+/// any other error.
+async fn main() -> ExitCode {
+    let cli = match Cli::try_parse() {
+        Ok(cli) => cli,
+        Err(e) if e.use_stderr() => {
+            let _ = e.print();
+            return ExitCode::from(3);
+        }
+        Err(e) => e.exit(),
+    };
+    let format = cli.format;
+    let command = match (cli.command, cli.config_file) {
+        (Some(command), _) => command,
+        (None, Some(config_file)) => Command::Run(RunArgs::new(config_file)),
+        (None, None) => {
+            eprintln!("Missing config file, see ronde --help");
+            return ExitCode::from(3);
+        }
+    };
+    let result = match command {
+        Command::CheckConfig { config_file } => {
+            logging::init(&LoggingConfig::default(), cli.verbose);
+            let format = format.unwrap_or_else(|| ConfigFormat::from_path(&config_file));
+            let problems = check_config(&config_file, format).await;
+            for problem in problems.iter() {
+                println!("{problem}");
+            }
+            if problems.is_empty() {
+                Ok(Outcome::Ok)
+            } else {
+                eprintln!("{} problem(s) found", problems.len());
+                Ok(Outcome::Failure)
+            }
+        }
+        command => match load_config(command.config_file(), format).await {
+            Ok(config) => {
+                logging::init(&config.logging, cli.verbose);
+                dispatch(command, config).await
+            }
+            Err(e) => Err(e),
+        },
+    };
+    match result {
+        Ok(outcome) => ExitCode::from(outcome.exit_code()),
+        Err(e) => {
+            eprintln!("Error: {e}");
+            ExitCode::from(3)
+        }
+    }
+}
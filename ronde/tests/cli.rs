@@ -0,0 +1,205 @@
+use assert_cmd::prelude::*;
+use predicates::prelude::*;
+use std::path::PathBuf;
+use std::process::Command;
+
+#[test]
+fn subcommands() -> Result<(), Box<dyn std::error::Error>> {
+    // Generate a temporary directory to store everything
+    let temp_dir = tempfile::tempdir()?;
+    let dir = temp_dir.path().to_str().unwrap();
+    let mut filepath = PathBuf::from(temp_dir.path());
+    filepath.push("config.toml");
+    std::fs::write(
+        &filepath,
+        format!(
+            r#"
+name = "Ronde"
+output_dir = "{dir}"
+history_file = "{dir}/history.json"
+[[commands]]
+  name = "ok"
+  run = "true"
+  tags = ["good"]
+[[commands]]
+  name = "ko"
+  run = "false"
+  tags = ["bad"]
+[[commands]]
+  name = "warn"
+  run = "false"
+  tags = ["bad"]
+  severity = "warning"
+"#
+        ),
+    )?;
+    let config = filepath.to_str().unwrap();
+    let history_file = temp_dir.path().join("history.json");
+
+    // A dry run does not write anything
+    Command::cargo_bin("ronde")?
+        .args(["run", "--dry-run", config])
+        .assert()
+        .code(2)
+        .stdout(predicate::str::contains("ok    ok"))
+        .stdout(predicate::str::contains("FAIL  ko"));
+    assert!(!history_file.exists());
+
+    // Only run the successful command
+    Command::cargo_bin("ronde")?
+        .args(["run", "--tag", "good", config])
+        .assert()
+        .success();
+    Command::cargo_bin("ronde")?
+        .args(["status", config])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("ko: never run"));
+
+    Command::cargo_bin("ronde")?
+        .args(["run", "--only", "ko", config])
+        .assert()
+        .code(2);
+    Command::cargo_bin("ronde")?
+        .args(["status", config])
+        .assert()
+        .code(2)
+        .stdout(predicate::str::contains("ok    ok"))
+        .stdout(predicate::str::contains("FAIL  ko"));
+
+    Command::cargo_bin("ronde")?
+        .args(["history", config, "ok"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("\"command\": \"true\""));
+
+    Command::cargo_bin("ronde")?
+        .args(["run", "--only", "unknown", config])
+        .assert()
+        .code(3)
+        .stderr(predicate::str::contains("Unknown command unknown"));
+    // Only warnings
+    Command::cargo_bin("ronde")?
+        .args([
+            "run",
+            "--dry-run",
+            "--only",
+            "warn",
+            "--output",
+            "tap",
+            config,
+        ])
+        .assert()
+        .code(1)
+        .stdout(predicate::str::starts_with(
+            "TAP version 13\n1..1\nnot ok 1 - warn\n",
+        ));
+    Command::cargo_bin("ronde")?
+        .args([
+            "run",
+            "--dry-run",
+            "--tag",
+            "good",
+            "--output",
+            "json",
+            config,
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("\"nb_ok\": 1"));
+    Ok(())
+}
+
+#[test]
+fn logging() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = tempfile::tempdir()?;
+    let dir = temp_dir.path().to_str().unwrap();
+    let config = format!(
+        r#"
+name = "Ronde"
+output_dir = "{dir}"
+history_file = "{dir}/history.json"
+[[commands]]
+  name = "ok"
+  run = "true"
+"#
+    );
+    let text_path = temp_dir.path().join("text.toml");
+    std::fs::write(&text_path, &config)?;
+    let json_path = temp_dir.path().join("json.toml");
+    std::fs::write(
+        &json_path,
+        config.replace(
+            "[[commands]]",
+            "[logging]\nformat = \"json\"\nlevel = \"debug\"\n[[commands]]",
+        ),
+    )?;
+
+    // Quiet by default
+    Command::cargo_bin("ronde")?
+        .args(["run", text_path.to_str().unwrap()])
+        .assert()
+        .success()
+        .stderr(predicate::str::is_empty());
+
+    Command::cargo_bin("ronde")?
+        .args(["-v", "run", text_path.to_str().unwrap()])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("starting command"))
+        .stderr(predicate::str::contains("history saved").not());
+
+    let output = Command::cargo_bin("ronde")?
+        .args(["run", json_path.to_str().unwrap()])
+        .output()?;
+    assert!(output.status.success());
+    let stderr = String::from_utf8(output.stderr)?;
+    let events = stderr
+        .lines()
+        .map(serde_json::from_str::<serde_json::Value>)
+        .collect::<Result<Vec<_>, _>>()?;
+    assert!(events
+        .iter()
+        .any(|e| e["fields"]["message"] == "command succeeded" && e["fields"]["command"] == "ok"));
+    assert!(events
+        .iter()
+        .any(|e| e["fields"]["message"] == "history saved"));
+    Ok(())
+}
+
+#[test]
+fn failing_channel() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = tempfile::tempdir()?;
+    let dir = temp_dir.path().to_str().unwrap();
+    let filepath = temp_dir.path().join("config.toml");
+    std::fs::write(
+        &filepath,
+        format!(
+            r#"
+name = "Ronde"
+output_dir = "{dir}"
+history_file = "{dir}/history.json"
+[[notifications.channels]]
+  type = "exec"
+  run = "exit 1"
+[[commands]]
+  name = "ko"
+  run = "false"
+"#
+        ),
+    )?;
+    let config = filepath.to_str().unwrap();
+
+    // The history is saved even though the notification failed
+    Command::cargo_bin("ronde")?
+        .args(["run", config])
+        .assert()
+        .code(3);
+    assert!(temp_dir.path().join("history.json").exists());
+    Command::cargo_bin("ronde")?
+        .args(["status", config])
+        .assert()
+        .code(2)
+        .stdout(predicate::str::contains("FAIL  ko"));
+    Ok(())
+}
@@ -41,9 +41,10 @@ history_file = "{dir}/history.toml"
     drop(cfg_file);
 
     cmd.arg(filepath.to_str().unwrap());
-    cmd.assert().success();
+    // Some commands fail
+    cmd.assert().code(2);
 
     // Run twice
-    cmd.assert().success();
+    cmd.assert().code(2);
     Ok(())
 }
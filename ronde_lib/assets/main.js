@@ -74,6 +74,16 @@ function processCommands(commands) {
           details.appendChild(p_timeout);
         }
 
+        if (d['u'] !== undefined) {
+          const u = d['u'];
+          const p_usage = document.createElement('p');
+          const user = (u.user_time / 1000000).toFixed(3);
+          const sys = (u.system_time / 1000000).toFixed(3);
+          p_usage.innerHTML = `Resources: user ${user}s, sys ${sys}s, max RSS ${u.max_rss} kB, `
+            + `context switches ${u.voluntary_switches} voluntary / ${u.involuntary_switches} involuntary`;
+          details.appendChild(p_usage);
+        }
+
         if (d['m'] !== undefined) {
           const p_message = document.createElement('p');
           p_message.innerHTML = 'Error message:';
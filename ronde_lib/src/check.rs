@@ -0,0 +1,634 @@
+use crate::config::{
+    ChannelConfig, CommandConfig, Config, ConfigFormat, DefaultRunnerEnv, ExecConfig, Location,
+    Priorities, PushoverConfig, Run, SmtpConfig,
+};
+use crate::user::{group_exists, User};
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::fmt;
+use std::ops::RangeInclusive;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+
+/// Default search path of `execvp` when `PATH` is not set
+const DEFAULT_PATH: &str = "/bin:/usr/bin";
+
+/// Shell keywords and builtins, which are not looked up in `PATH`
+const SHELL_BUILTINS: &[&str] = &[
+    "!", "[[", "case", "cd", "command", "eval", "exec", "exit", "export", "for", "if", "read",
+    "set", "source", "test", "until", "while", ".", ":",
+];
+
+/// Problem found in the configuration
+#[derive(Debug, Clone, PartialEq)]
+pub struct Problem {
+    /// Where the faulty setting is defined, if known
+    pub location: Option<Location>,
+    /// Description of the problem
+    pub message: String,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.location {
+            Some(ref location) => write!(f, "{}: {}", location, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+/// Problems found in the configuration, along with the location of the
+/// top-level settings
+struct Checker {
+    /// Line of each top-level key, of the keys of top-level tables and of
+    /// the items of their arrays, like `output_dir`, `notifications.pushover`
+    /// or `notifications.channels.0`
+    keys: HashMap<String, Location>,
+    /// Problems found so far
+    problems: Vec<Problem>,
+}
+
+impl Checker {
+    /// Report a problem about a setting, located at the closest known key
+    fn global(&mut self, key: &str, message: String) {
+        let mut key = key;
+        let location = loop {
+            if let Some(location) = self.keys.get(key) {
+                break Some(location.clone());
+            }
+            match key.rsplit_once('.') {
+                Some((parent, _)) => key = parent,
+                None => break None,
+            }
+        };
+        self.problems.push(Problem { location, message });
+    }
+
+    /// Report a problem about a command
+    fn command(&mut self, command: &CommandConfig, message: String) {
+        self.problems.push(Problem {
+            location: command.location.clone(),
+            message: format!("command {}: {}", command.name, message),
+        });
+    }
+}
+
+/// Get the line of the top-level keys of a TOML document, of the keys of
+/// its top-level tables, and of the items of the arrays of these tables
+fn key_locations(path: &str, contents: &str) -> HashMap<String, Location> {
+    let mut keys = HashMap::new();
+    let Ok(document) = toml::de::DeTable::parse(contents) else {
+        return keys;
+    };
+    let line = |start: usize| Location {
+        file: path.to_string(),
+        line: Some(contents[..start].matches('\n').count() + 1),
+    };
+    for (key, value) in document.get_ref() {
+        keys.insert(key.get_ref().to_string(), line(key.span().start));
+        if let toml::de::DeValue::Table(table) = value.get_ref() {
+            for (subkey, subvalue) in table {
+                let name = format!("{}.{}", key.get_ref(), subkey.get_ref());
+                if let toml::de::DeValue::Array(items) = subvalue.get_ref() {
+                    for (idx, item) in items.iter().enumerate() {
+                        keys.insert(format!("{name}.{idx}"), line(item.span().start));
+                    }
+                }
+                keys.insert(name, line(subkey.span().start));
+            }
+        }
+    }
+    keys
+}
+
+/// Get the program run by a shell command line, if it is a simple command
+fn shell_program(command_line: &str) -> Option<&str> {
+    let word = command_line
+        .split_whitespace()
+        .find(|w| !w.contains('=') || w.starts_with('='))?;
+    let special = |c: char| "$`'\"\\()&|;<>{}*?~".contains(c);
+    if word.contains(special) || SHELL_BUILTINS.contains(&word) {
+        None
+    } else {
+        Some(word)
+    }
+}
+
+/// Prefix `path` with the chroot, if any
+fn in_root(root: Option<&str>, path: &Path) -> PathBuf {
+    match root {
+        Some(root) => Path::new(root).join(path.strip_prefix("/").unwrap_or(path)),
+        None => path.to_path_buf(),
+    }
+}
+
+/// Whether `path` is an executable file
+fn is_executable(path: &Path) -> bool {
+    path.metadata()
+        .map(|m| m.is_file() && m.mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+/// Look `program` up like `execvp` would
+fn find_executable(program: &str, search_path: &str, root: Option<&str>, cwd: &Path) -> bool {
+    if program.contains('/') {
+        return is_executable(&in_root(root, &cwd.join(program)));
+    }
+    search_path
+        .split(':')
+        .map(|dir| if dir.is_empty() { "." } else { dir })
+        .any(|dir| is_executable(&in_root(root, &cwd.join(dir).join(program))))
+}
+
+/// Whether a user with this UID exists
+fn user_exists(uid: u32) -> bool {
+    matches!(User::from_uid(uid), Ok(Some(_)))
+}
+
+/// Whether `path` can be written by `uid`/`gid`, or by the current user
+fn is_writable(path: &Path, uid: Option<u32>, gid: Option<u32>) -> bool {
+    match uid {
+        None => {
+            let Ok(path) = CString::new(path.as_os_str().as_encoded_bytes()) else {
+                return false;
+            };
+            unsafe { libc::access(path.as_ptr(), libc::W_OK) == 0 }
+        }
+        Some(0) => path.exists(),
+        Some(uid) => match path.metadata() {
+            Ok(m) if m.uid() == uid => m.mode() & 0o200 != 0,
+            Ok(m) if Some(m.gid()) == gid => m.mode() & 0o020 != 0,
+            Ok(m) => m.mode() & 0o002 != 0,
+            Err(_) => false,
+        },
+    }
+}
+
+/// Whether `value` looks like a Pushover user key or API token
+fn is_pushover_key(value: &str) -> bool {
+    value.len() == 30 && value.chars().all(|c| c.is_ascii_alphanumeric())
+}
+
+/// Whether `priority` is a Pushover priority, from -2 to 2
+fn is_pushover_priority(priority: i8) -> bool {
+    (-2..=2).contains(&priority)
+}
+
+impl Checker {
+    /// Check where ronde writes its files
+    fn check_output(&mut self, config: &Config) {
+        let output_dir = Path::new(&config.output_dir);
+        if !output_dir.is_dir() {
+            self.global(
+                "output_dir",
+                format!("output_dir {} is not a directory", config.output_dir),
+            );
+        } else if !is_writable(output_dir, config.uid, config.gid) {
+            self.global(
+                "output_dir",
+                format!("output_dir {} is not writable", config.output_dir),
+            );
+        }
+        let history_file = Path::new(&config.history_file);
+        let writable = if history_file.exists() {
+            is_writable(history_file, config.uid, config.gid)
+        } else {
+            match history_file.parent() {
+                Some(dir) if dir.as_os_str().is_empty() => {
+                    is_writable(Path::new("."), config.uid, config.gid)
+                }
+                Some(dir) => dir.is_dir() && is_writable(dir, config.uid, config.gid),
+                None => false,
+            }
+        };
+        if !writable {
+            self.global(
+                "history_file",
+                format!("history_file {} is not writable", config.history_file),
+            );
+        }
+        if let Some(uid) = config.uid {
+            if !user_exists(uid) {
+                self.global("uid", format!("unknown uid {uid}"));
+            }
+        }
+        if let Some(gid) = config.gid {
+            if !group_exists(gid) {
+                self.global("gid", format!("unknown gid {gid}"));
+            }
+        }
+    }
+
+    /// Check the syntax of the Pushover credentials
+    fn check_pushover(&mut self, key: &str, pushover: &PushoverConfig) {
+        if !is_pushover_key(&pushover.user) {
+            self.global(
+                key,
+                "pushover user key must be 30 alphanumeric characters".to_string(),
+            );
+        }
+        if !is_pushover_key(&pushover.token) {
+            self.global(
+                key,
+                "pushover token must be 30 alphanumeric characters".to_string(),
+            );
+        }
+        if let Some(ref url) = pushover.url {
+            if let Err(e) = reqwest::Url::parse(url) {
+                self.global(key, format!("invalid pushover url {url}: {e}"));
+            }
+        }
+        let options = [
+            &pushover.options,
+            &pushover.failure,
+            &pushover.continuous_failure,
+            &pushover.back_from_failure,
+        ];
+        for priority in options.iter().filter_map(|o| o.priority) {
+            if !is_pushover_priority(priority) {
+                self.global(key, format!("unknown pushover priority {priority}"));
+            }
+        }
+    }
+
+    /// Check the priorities of a channel against the range it accepts
+    fn check_priorities(
+        &mut self,
+        key: &str,
+        channel: &ChannelConfig,
+        priorities: &Priorities,
+        range: RangeInclusive<u8>,
+    ) {
+        let priorities = [
+            priorities.failure,
+            priorities.continuous_failure,
+            priorities.back_from_failure,
+        ];
+        for priority in priorities.into_iter().flatten() {
+            if !range.contains(&priority) {
+                self.global(
+                    key,
+                    format!("unknown {} priority {priority}", channel.notifier().name()),
+                );
+            }
+        }
+    }
+
+    /// Check the URL of a notification channel
+    fn check_url(&mut self, key: &str, channel: &ChannelConfig, url: &str) {
+        if let Err(e) = reqwest::Url::parse(url) {
+            // The URL may hold a secret: only name the channel
+            self.global(
+                key,
+                format!(
+                    "invalid url of the {} channel: {e}",
+                    channel.notifier().name()
+                ),
+            );
+        }
+    }
+
+    /// Check that the program of an exec channel can be found
+    fn check_exec(&mut self, key: &str, exec: &ExecConfig) {
+        let program = match exec.run {
+            Run::Shell(ref command_line) => shell_program(command_line),
+            Run::Argv(ref argv) => argv.first().map(String::as_str),
+        };
+        let Some(program) = program else {
+            self.global(key, "exec channel with nothing to run".to_string());
+            return;
+        };
+        let search_path = std::env::var("PATH").unwrap_or_else(|_| DEFAULT_PATH.to_string());
+        let cwd = std::env::current_dir().unwrap_or_default();
+        if !find_executable(program, &search_path, None, &cwd) {
+            self.global(key, format!("{program} not found in PATH {search_path}"));
+        }
+    }
+
+    /// Check the addresses of an email channel
+    fn check_smtp(&mut self, key: &str, smtp: &SmtpConfig) {
+        if smtp.to.is_empty() {
+            self.global(key, "smtp channel without recipient".to_string());
+        }
+        for address in std::iter::once(&smtp.from).chain(smtp.to.iter()) {
+            if let Err(e) = address.parse::<lettre::message::Mailbox>() {
+                self.global(key, format!("invalid email address {address}: {e}"));
+            }
+        }
+    }
+
+    /// Check that a command can be run
+    fn check_command(&mut self, command: &CommandConfig, defaults: &DefaultRunnerEnv) {
+        if let Some(priority) = command
+            .notifications
+            .as_ref()
+            .and_then(|n| n.pushover.as_ref())
+            .and_then(|p| p.priority)
+        {
+            if !is_pushover_priority(priority) {
+                self.command(command, format!("unknown pushover priority {priority}"));
+            }
+        }
+        let root = command.get_chroot(defaults);
+        if let Some(ref root) = root {
+            if !Path::new(root).is_dir() {
+                self.command(command, format!("chroot {root} is not a directory"));
+                return;
+            }
+        }
+        let root = root.as_deref();
+        let cwd = command.get_cwd(defaults);
+        if let Some(ref cwd) = cwd {
+            if !in_root(root, Path::new(cwd)).is_dir() {
+                self.command(command, format!("cwd {cwd} is not a directory"));
+            }
+        }
+        let cwd = match cwd {
+            Some(cwd) => PathBuf::from(cwd),
+            None if root.is_some() => PathBuf::from("/"),
+            None => std::env::current_dir().unwrap_or_default(),
+        };
+        let search_path = match command
+            .get_env(defaults)
+            .and_then(|e| e.get("PATH").cloned())
+        {
+            Some(path) => path,
+            None if command.get_clear_env(defaults) => DEFAULT_PATH.to_string(),
+            None => std::env::var("PATH").unwrap_or_else(|_| DEFAULT_PATH.to_string()),
+        };
+        let programs = match (command.run.as_ref(), command.interpreter.as_ref()) {
+            (Some(Run::Shell(command_line)), _) => {
+                let mut programs = vec![command.get_shell(defaults)];
+                programs.extend(shell_program(command_line).map(str::to_string));
+                programs
+            }
+            (Some(Run::Argv(argv)), _) => argv.first().cloned().into_iter().collect(),
+            (None, Some(interpreter)) => vec![interpreter.clone()],
+            (None, None) => vec![command.get_shell(defaults)],
+        };
+        for program in programs {
+            if !find_executable(&program, &search_path, root, &cwd) {
+                self.command(
+                    command,
+                    format!("{program} not found in PATH {search_path}"),
+                );
+            }
+        }
+        if let Some(uid) = command.get_uid(defaults) {
+            if !user_exists(uid) {
+                self.command(command, format!("unknown uid {uid}"));
+            }
+        }
+        if let Some(gid) = command.get_gid(defaults) {
+            if !group_exists(gid) {
+                self.command(command, format!("unknown gid {gid}"));
+            }
+        }
+    }
+}
+
+/// Load and validate a configuration file without running anything
+///
+/// Every problem found is returned, not only the first one. If the
+/// configuration cannot be parsed, the parsing error is the only problem.
+pub async fn check_config(path: &str, format: ConfigFormat) -> Vec<Problem> {
+    let mut config = match Config::parse_with_format(path, format).await {
+        Ok(config) => config,
+        Err(e) => {
+            return vec![Problem {
+                location: None,
+                message: e.to_string(),
+            }]
+        }
+    };
+    let mut problems: Vec<Problem> = config
+        .validate()
+        .await
+        .into_iter()
+        .map(|(location, e)| Problem {
+            location,
+            message: e.to_string(),
+        })
+        .collect();
+    // Only TOML files keep track of where settings are defined
+    let keys = match format {
+        ConfigFormat::Toml => {
+            let contents = tokio::fs::read_to_string(path).await.unwrap_or_default();
+            key_locations(path, &contents)
+        }
+        ConfigFormat::Yaml | ConfigFormat::Json => HashMap::new(),
+    };
+    let mut checker = Checker {
+        keys,
+        problems: Vec::new(),
+    };
+    checker.check_output(&config);
+    if let Some(ref notifications) = config.notifications {
+        if let Some(ref pushover) = notifications.pushover {
+            checker.check_pushover("notifications.pushover", pushover);
+        }
+        for (idx, channel) in notifications.channels.iter().enumerate() {
+            let key = &format!("notifications.channels.{idx}");
+            match channel {
+                ChannelConfig::Pushover(pushover) => checker.check_pushover(key, pushover),
+                ChannelConfig::Webhook(webhook) => checker.check_url(key, channel, &webhook.url),
+                ChannelConfig::Smtp(smtp) => checker.check_smtp(key, smtp),
+                ChannelConfig::Ntfy(ntfy) => {
+                    if let Some(ref server) = ntfy.server {
+                        checker.check_url(key, channel, server)
+                    }
+                    checker.check_priorities(key, channel, &ntfy.priorities, 1..=5);
+                }
+                ChannelConfig::Gotify(gotify) => {
+                    checker.check_url(key, channel, &gotify.server);
+                    checker.check_priorities(key, channel, &gotify.priorities, 0..=10);
+                }
+                ChannelConfig::Slack(slack) => checker.check_url(key, channel, &slack.webhook_url),
+                ChannelConfig::Discord(discord) => {
+                    checker.check_url(key, channel, &discord.webhook_url)
+                }
+                ChannelConfig::Matrix(matrix) => {
+                    checker.check_url(key, channel, &matrix.homeserver)
+                }
+                ChannelConfig::Telegram(telegram) => {
+                    if let Some(ref api_url) = telegram.api_url {
+                        checker.check_url(key, channel, api_url)
+                    }
+                }
+                ChannelConfig::Exec(exec) => checker.check_exec(key, exec),
+                ChannelConfig::PagerDuty(pagerduty) => {
+                    if let Some(ref url) = pagerduty.url {
+                        checker.check_url(key, channel, url)
+                    }
+                }
+                ChannelConfig::Opsgenie(opsgenie) => {
+                    if let Some(ref api_url) = opsgenie.api_url {
+                        checker.check_url(key, channel, api_url)
+                    }
+                }
+                ChannelConfig::Alertmanager(alertmanager) => {
+                    checker.check_url(key, channel, &alertmanager.url)
+                }
+            }
+        }
+    }
+    for command in &config.commands {
+        checker.check_command(command, &config.default_env);
+    }
+    problems.extend(checker.problems);
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shell_program() {
+        assert_eq!(shell_program("ping -c 4 localhost"), Some("ping"));
+        assert_eq!(shell_program("LANG=C  /usr/bin/df -h"), Some("/usr/bin/df"));
+        assert_eq!(shell_program("cd /tmp && ls"), None);
+        assert_eq!(shell_program("$HOME/bin/check"), None);
+        assert_eq!(shell_program("(true)"), None);
+    }
+
+    #[test]
+    fn test_key_locations() {
+        let keys = key_locations(
+            "config.toml",
+            r#"name = "Ronde"
+output_dir = "/var/www/html"
+
+[notifications]
+minutes_between_continuous_failure_notification = 10
+[notifications.pushover]
+user = "user"
+[[notifications.channels]]
+type = "slack"
+[[notifications.channels]]
+type = "discord"
+"#,
+        );
+        assert_eq!(keys["output_dir"].to_string(), "config.toml:2");
+        assert_eq!(
+            keys["notifications.minutes_between_continuous_failure_notification"].line,
+            Some(5)
+        );
+        assert_eq!(keys["notifications.pushover"].line, Some(6));
+        assert_eq!(keys["notifications.channels.0"].line, Some(8));
+        assert_eq!(keys["notifications.channels.1"].line, Some(10));
+    }
+
+    #[tokio::test]
+    async fn test_check_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        std::fs::write(
+            &path,
+            format!(
+                r#"name = "Ronde"
+output_dir = "{dir}/missing"
+history_file = "{dir}/history.toml"
+[notifications.pushover]
+user = "not a key"
+token = "abcdefghijklmnopqrstuvwxyz0123"
+priority = 3
+[[notifications.channels]]
+type = "slack"
+webhook_url = "hooks.example.com/s3cr3t"
+[[notifications.channels]]
+type = "gotify"
+server = "https://gotify.example.com"
+token = "token"
+priorities.failure = 11
+[[commands]]
+    name = "ok"
+    run = "LANG=C true --version"
+    notifications.pushover.priority = -3
+[[commands]]
+    name = "missing"
+    run = ["ronde-missing-executable", "--help"]
+    cwd = "{dir}/missing"
+"#,
+                dir = dir.path().display()
+            ),
+        )
+        .unwrap();
+        let path = path.to_str().unwrap();
+        let problems: Vec<String> = check_config(path, ConfigFormat::Toml)
+            .await
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        let dir = dir.path().display();
+        assert_eq!(
+            problems,
+            vec![
+                format!("{path}:2: output_dir {dir}/missing is not a directory"),
+                format!("{path}:4: pushover user key must be 30 alphanumeric characters"),
+                format!("{path}:4: unknown pushover priority 3"),
+                format!("{path}:8: invalid url of the slack channel: relative URL without a base"),
+                format!("{path}:11: unknown gotify priority 11"),
+                format!("{path}:16: command ok: unknown pushover priority -3"),
+                format!("{path}:20: command missing: cwd {dir}/missing is not a directory"),
+                format!(
+                    "{path}:20: command missing: ronde-missing-executable not found in PATH {}",
+                    std::env::var("PATH").unwrap()
+                ),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_check_config_collects_load_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        std::fs::write(
+            &path,
+            format!(
+                r#"name = "Ronde"
+output_dir = "{dir}"
+history_file = "{dir}/history.toml"
+[[commands]]
+    name = "twice"
+    run = "true"
+[[commands]]
+    name = "twice"
+    run = "true"
+[[commands]]
+    name = "nothing"
+[[commands]]
+    name = "user"
+    run = "true"
+    user = "no such user"
+[[commands]]
+    name = "secret"
+    run = "true"
+    env.TOKEN = "${{RONDE_UNDEFINED_VARIABLE}}"
+"#,
+                dir = dir.path().display()
+            ),
+        )
+        .unwrap();
+        let path = path.to_str().unwrap();
+        let problems: Vec<String> = check_config(path, ConfigFormat::Toml)
+            .await
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        let expected = [
+            (12, "Command user: Unknown user no such user"),
+            (16, "RONDE_UNDEFINED_VARIABLE"),
+            (7, "twice"),
+            (10, "Command nothing: neither run nor script is set"),
+        ];
+        assert_eq!(problems.len(), expected.len(), "{problems:?}");
+        for (problem, (line, expected)) in problems.iter().zip(expected) {
+            assert!(
+                problem.starts_with(&format!("{path}:{line}: ")),
+                "{problem}"
+            );
+            assert!(problem.contains(expected), "{problem}");
+        }
+    }
+}
@@ -1,10 +1,15 @@
-use serde_derive::Deserialize;
+use crate::matrix::{self, Matrix, MatrixError, TargetsFormat};
+use crate::secrets::{interpolate, parse_env_file, SecretError, Secrets};
+use crate::template::{self, TemplateError};
+use crate::user::{group_from_name, User, UserError};
+use serde_derive::{Deserialize, Serialize};
 use snafu::prelude::*;
-use std::collections::{HashMap, HashSet};
+use std::collections::HashMap;
+use std::path::Path;
 use tokio::fs;
 
 /// Timeout in seconds
-#[derive(Debug, PartialEq, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Deserialize)]
 pub struct Timeout(pub u16);
 
 impl Default for Timeout {
@@ -13,7 +18,225 @@ impl Default for Timeout {
     }
 }
 
-#[derive(Debug, Default, PartialEq, Deserialize)]
+/// Resource limits applied to a command
+///
+/// Each limit is set as both the soft and the hard limit.
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+pub struct ResourceLimits {
+    /// Maximum size of the address space, in bytes (`RLIMIT_AS`)
+    pub address_space: Option<u64>,
+    /// Maximum number of processes of the user (`RLIMIT_NPROC`)
+    pub nproc: Option<u64>,
+    /// Maximum number of open file descriptors (`RLIMIT_NOFILE`)
+    pub nofile: Option<u64>,
+    /// Maximum CPU time, in seconds (`RLIMIT_CPU`)
+    pub cpu: Option<u64>,
+}
+
+impl ResourceLimits {
+    /// Merge the limits with the defaults, the limits set here taking
+    /// precedence
+    pub fn merge(&self, defaults: &ResourceLimits) -> ResourceLimits {
+        ResourceLimits {
+            address_space: self.address_space.or(defaults.address_space),
+            nproc: self.nproc.or(defaults.nproc),
+            nofile: self.nofile.or(defaults.nofile),
+            cpu: self.cpu.or(defaults.cpu),
+        }
+    }
+}
+
+/// Linux namespace that can be unshared before running a command
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Namespace {
+    /// Network namespace: the command only sees a loopback interface
+    Network,
+    /// Mount namespace: mounts done by the command are not visible outside
+    Mount,
+    /// PID namespace: the command only sees its own processes
+    Pid,
+}
+
+/// What to run for a command
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub enum Run {
+    /// Command line run through a shell, `sh -c` by default
+    Shell(String),
+    /// Program and its arguments, executed directly without a shell
+    Argv(Vec<String>),
+}
+
+impl From<&str> for Run {
+    fn from(command_line: &str) -> Self {
+        Run::Shell(command_line.to_string())
+    }
+}
+
+/// Quote an argument so that it can be pasted into a shell
+pub(crate) fn shell_quote(arg: &str) -> String {
+    let is_safe = |c: char| c.is_ascii_alphanumeric() || "_-./=:,+@%".contains(c);
+    if !arg.is_empty() && arg.chars().all(is_safe) {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', "'\\''"))
+    }
+}
+
+impl std::fmt::Display for Run {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Run::Shell(command_line) => write!(f, "{command_line}"),
+            Run::Argv(argv) => {
+                let quoted: Vec<String> = argv.iter().map(|arg| shell_quote(arg)).collect();
+                write!(f, "{}", quoted.join(" "))
+            }
+        }
+    }
+}
+
+/// Where something is defined in the configuration files
+#[derive(Debug, Clone, PartialEq)]
+pub struct Location {
+    /// Path of the file
+    pub file: String,
+    /// Line number, starting at 1, only known for TOML files
+    pub line: Option<usize>,
+}
+
+impl std::fmt::Display for Location {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "{}:{}", self.file, line),
+            None => write!(f, "{}", self.file),
+        }
+    }
+}
+
+/// Format of a configuration file
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConfigFormat {
+    /// TOML, the default
+    #[default]
+    Toml,
+    /// YAML
+    Yaml,
+    /// JSON
+    Json,
+}
+
+impl ConfigFormat {
+    /// Guess the format of a configuration file from its extension
+    pub fn from_path(path: &str) -> Self {
+        match Path::new(path).extension().and_then(|e| e.to_str()) {
+            Some("yaml") | Some("yml") => ConfigFormat::Yaml,
+            Some("json") => ConfigFormat::Json,
+            _ => ConfigFormat::Toml,
+        }
+    }
+
+    /// Parse the content of a configuration file into a TOML table
+    fn parse(self, path: &str, contents: &str) -> Result<toml::Table, ConfigError> {
+        match self {
+            ConfigFormat::Toml => toml::from_str(contents).context(SerdeTomlSnafu {
+                path: path.to_string(),
+            }),
+            ConfigFormat::Yaml => {
+                let mut value = serde_yaml::from_str(contents).context(SerdeYamlSnafu {
+                    path: path.to_string(),
+                })?;
+                drop_yaml_nulls(&mut value);
+                serde_yaml::from_value(value).context(SerdeYamlSnafu {
+                    path: path.to_string(),
+                })
+            }
+            ConfigFormat::Json => {
+                let mut value = serde_json::from_str(contents).context(SerdeJsonSnafu {
+                    path: path.to_string(),
+                })?;
+                drop_json_nulls(&mut value);
+                serde_json::from_value(value).context(SerdeJsonSnafu {
+                    path: path.to_string(),
+                })
+            }
+        }
+    }
+}
+
+/// Remove the null values of YAML mappings, like `key: ~` or `key:`, which
+/// TOML cannot represent: such keys are left unset
+fn drop_yaml_nulls(value: &mut serde_yaml::Value) {
+    match value {
+        serde_yaml::Value::Mapping(mapping) => {
+            mapping.retain(|_, v| !v.is_null());
+            mapping.values_mut().for_each(drop_yaml_nulls);
+        }
+        serde_yaml::Value::Sequence(sequence) => sequence.iter_mut().for_each(drop_yaml_nulls),
+        _ => {}
+    }
+}
+
+/// Remove the null values of JSON objects, like `"key": null`, which TOML
+/// cannot represent: such keys are left unset
+fn drop_json_nulls(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::Object(object) => {
+            object.retain(|_, v| !v.is_null());
+            object.values_mut().for_each(drop_json_nulls);
+        }
+        serde_json::Value::Array(array) => array.iter_mut().for_each(drop_json_nulls),
+        _ => {}
+    }
+}
+
+impl std::str::FromStr for ConfigFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "toml" => Ok(ConfigFormat::Toml),
+            "yaml" | "yml" => Ok(ConfigFormat::Yaml),
+            "json" => Ok(ConfigFormat::Json),
+            _ => Err(format!("unknown configuration format {s}")),
+        }
+    }
+}
+
+/// How bad it is when a command fails
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// The failure is a warning
+    Warning,
+    /// The failure is critical, the default
+    #[default]
+    Critical,
+}
+
+impl std::fmt::Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Severity::Warning => write!(f, "warning"),
+            Severity::Critical => write!(f, "critical"),
+        }
+    }
+}
+
+/// Notification settings of a command, overriding the global ones
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+pub struct CommandNotificationConfig {
+    /// Whether to send notifications about this command.
+    /// Default is true
+    pub enabled: Option<bool>,
+    /// Notify on failure every minutes
+    pub minutes_between_continuous_failure_notification: Option<i64>,
+    /// Pushover options of the notifications of this command
+    pub pushover: Option<PushoverOptions>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
 /// Command configuration
 pub struct CommandConfig {
     /// Name of the command
@@ -22,22 +245,75 @@ pub struct CommandConfig {
     #[serde(default)]
     pub timeout: Timeout,
     /// Command to run
-    pub run: String,
+    pub run: Option<Run>,
+    /// Shell used to run `run` when it is a string, `sh` by default
+    pub shell: Option<String>,
+    /// Program used to run `script`, the shell by default
+    pub interpreter: Option<String>,
+    /// Inline script to run instead of `run`
+    pub script: Option<String>,
+    /// Data written to the standard input of the command.
+    /// If neither `stdin` nor `stdin_file` is set, the standard input is
+    /// `/dev/null`.
+    pub stdin: Option<String>,
+    /// File whose content is written to the standard input of the command
+    pub stdin_file: Option<String>,
+    /// Maximum number of bytes of stdout and of stderr to keep
+    pub max_output_bytes: Option<usize>,
     /// UID to use to run the command
     pub uid: Option<u32>,
     /// GID to use to run the command
     pub gid: Option<u32>,
+    /// Name of the user to run the command as, instead of `uid`
+    pub user: Option<String>,
+    /// Name of the group to run the command as, instead of `gid`.
+    /// Defaults to the primary group of `user`
+    pub group: Option<String>,
     /// Clears the entire environment map before running the command
     #[serde(default)]
     pub clear_env: bool,
     /// Environment variables to set
     #[serde(default)]
     pub env: Option<HashMap<String, String>>,
+    /// Dotenv file to read environment variables from.
+    /// Variables set in `env` take precedence.
+    pub env_file: Option<String>,
+    /// Variables of `env_file` whose values are secret, to be redacted
+    #[serde(default)]
+    pub secret_env: Vec<String>,
     /// Working directory
     pub cwd: Option<String>,
+    /// Resource limits
+    pub rlimits: Option<ResourceLimits>,
+    /// Set the `no_new_privs` flag before running the command
+    #[serde(default)]
+    pub no_new_privs: bool,
+    /// Directory to chroot into before running the command
+    pub chroot: Option<String>,
+    /// Linux namespaces to unshare before running the command
+    pub unshare: Option<Vec<Namespace>>,
+    /// Tags to group commands
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Severity of a failure of the command
+    #[serde(default)]
+    pub severity: Severity,
+    /// Notification settings specific to this command
+    pub notifications: Option<CommandNotificationConfig>,
+    /// Where the command is defined
+    #[serde(skip)]
+    pub location: Option<Location>,
 }
 
 impl CommandConfig {
+    /// Get where the command is defined, for error messages
+    pub fn location_str(&self) -> String {
+        match self.location {
+            Some(ref location) => location.to_string(),
+            None => "unknown location".to_string(),
+        }
+    }
+
     /// Get the UID to run the command based on the config and the defaults
     pub fn get_uid(&self, defaults: &DefaultRunnerEnv) -> Option<u32> {
         match (self.uid, defaults.uid) {
@@ -85,6 +361,121 @@ impl CommandConfig {
             }
         }
     }
+
+    /// Get the shell to run the command based on the config and the defaults
+    pub fn get_shell(&self, defaults: &DefaultRunnerEnv) -> String {
+        match (self.shell.as_ref(), defaults.shell.as_ref()) {
+            (None, None) => "sh".to_string(),
+            (None, Some(shell)) => shell.clone(),
+            (Some(shell), _) => shell.clone(),
+        }
+    }
+
+    /// Get a human readable version of what is run
+    pub fn command_line(&self) -> String {
+        match (self.run.as_ref(), self.script.as_ref()) {
+            (Some(run), _) => run.to_string(),
+            (None, Some(script)) => match self.interpreter {
+                Some(ref interpreter) => format!("#!{interpreter}\n{script}"),
+                None => script.clone(),
+            },
+            (None, None) => String::new(),
+        }
+    }
+
+    /// Check that the command defines exactly one thing to run
+    ///
+    /// A script is given to its interpreter as `/dev/fd/N`, which does not
+    /// exist in a chroot.
+    pub fn check_run(&self, defaults: &DefaultRunnerEnv) -> Result<(), ConfigError> {
+        let reason = match (self.run.as_ref(), self.script.as_ref()) {
+            (None, None) => "neither run nor script is set",
+            (Some(_), Some(_)) => "both run and script are set",
+            (Some(Run::Argv(argv)), None) if argv.is_empty() => "run is an empty array",
+            (Some(_), None) if self.interpreter.is_some() => "interpreter is set without script",
+            (None, Some(_)) if self.get_chroot(defaults).is_some() => {
+                "script cannot run in a chroot"
+            }
+            _ => return Ok(()),
+        };
+        Err(ConfigError::InvalidRun {
+            cmd: self.name.clone(),
+            reason: reason.to_string(),
+        })
+    }
+
+    /// Check that the command has at most one source for its standard input
+    pub fn check_stdin(&self) -> Result<(), ConfigError> {
+        if self.stdin.is_some() && self.stdin_file.is_some() {
+            return Err(ConfigError::InvalidStdin {
+                cmd: self.name.clone(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Get the maximum number of bytes of output to keep based on the config
+    /// and the defaults
+    pub fn get_max_output_bytes(&self, defaults: &DefaultRunnerEnv) -> Option<usize> {
+        self.max_output_bytes.or(defaults.max_output_bytes)
+    }
+
+    /// Whether notifications are enabled for this command
+    pub fn notifications_enabled(&self) -> bool {
+        self.notifications
+            .as_ref()
+            .and_then(|n| n.enabled)
+            .unwrap_or(true)
+    }
+
+    /// Get the delay between two notifications of a continuous failure
+    /// based on the config and the global notification settings
+    pub fn get_minutes_between_continuous_failure_notification(
+        &self,
+        defaults: &NotificationConfig,
+    ) -> i64 {
+        self.notifications
+            .as_ref()
+            .and_then(|n| n.minutes_between_continuous_failure_notification)
+            .unwrap_or(defaults.minutes_between_continuous_failure_notification)
+    }
+
+    /// Get the resource limits based on the config and the defaults
+    pub fn get_rlimits(&self, defaults: &DefaultRunnerEnv) -> ResourceLimits {
+        match (self.rlimits.as_ref(), defaults.rlimits.as_ref()) {
+            (None, None) => ResourceLimits::default(),
+            (Some(rl), None) => rl.clone(),
+            (None, Some(rl)) => rl.clone(),
+            (Some(rlc), Some(rld)) => rlc.merge(rld),
+        }
+    }
+
+    /// Whether to set the `no_new_privs` flag based on the config and the
+    /// defaults
+    pub fn get_no_new_privs(&self, defaults: &DefaultRunnerEnv) -> bool {
+        matches!(
+            (self.no_new_privs, defaults.no_new_privs),
+            (true, _) | (false, Some(true))
+        )
+    }
+
+    /// Get the directory to chroot into based on the config and the defaults
+    pub fn get_chroot(&self, defaults: &DefaultRunnerEnv) -> Option<String> {
+        match (self.chroot.as_ref(), defaults.chroot.as_ref()) {
+            (None, None) => None,
+            (None, Some(chroot)) => Some(chroot.clone()),
+            (Some(chroot), _) => Some(chroot.clone()),
+        }
+    }
+
+    /// Get the namespaces to unshare based on the config and the defaults
+    pub fn get_unshare(&self, defaults: &DefaultRunnerEnv) -> Vec<Namespace> {
+        match (self.unshare.as_ref(), defaults.unshare.as_ref()) {
+            (None, None) => Vec::new(),
+            (None, Some(ns)) => ns.clone(),
+            (Some(ns), _) => ns.clone(),
+        }
+    }
 }
 
 #[derive(Debug, Default, PartialEq, Deserialize)]
@@ -94,32 +485,602 @@ pub struct DefaultRunnerEnv {
     pub uid: Option<u32>,
     /// GID to use to run the command
     pub gid: Option<u32>,
+    /// Name of the user to run the command as, instead of `uid`
+    pub user: Option<String>,
+    /// Name of the group to run the command as, instead of `gid`.
+    /// Defaults to the primary group of `user`
+    pub group: Option<String>,
     /// Clears the entire environment map before running the command
     #[serde(default)]
     pub clear_env: Option<bool>,
     /// Environment variables to set
     #[serde(default)]
     pub env: Option<HashMap<String, String>>,
+    /// Dotenv file to read environment variables from.
+    /// Variables set in `env` take precedence.
+    pub env_file: Option<String>,
+    /// Variables of `env_file` whose values are secret, to be redacted
+    #[serde(default)]
+    pub secret_env: Vec<String>,
     /// Working directory
     pub cwd: Option<String>,
+    /// Shell used to run commands given as a string, `sh` by default
+    pub shell: Option<String>,
+    /// Maximum number of bytes of stdout and of stderr to keep
+    pub max_output_bytes: Option<usize>,
+    /// Resource limits
+    pub rlimits: Option<ResourceLimits>,
+    /// Set the `no_new_privs` flag before running the command
+    #[serde(default)]
+    pub no_new_privs: Option<bool>,
+    /// Directory to chroot into before running the command
+    pub chroot: Option<String>,
+    /// Linux namespaces to unshare before running the command
+    pub unshare: Option<Vec<Namespace>>,
+}
+
+/// How Pushover messages are delivered
+///
+/// Unset options are taken from the less specific settings, or left to the
+/// defaults of Pushover.
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+pub struct PushoverOptions {
+    /// Priority, from -2 (no alert) to 2 (emergency, repeated until
+    /// acknowledged)
+    pub priority: Option<i8>,
+    /// Name of the sound to play
+    pub sound: Option<String>,
+    /// Device to send to, instead of every device of the user
+    pub device: Option<String>,
+    /// Number of seconds after which the message is deleted
+    pub ttl: Option<u32>,
+    /// Format the message with HTML instead of a monospace font
+    pub html: Option<bool>,
+}
+
+impl PushoverOptions {
+    /// Fill the unset options with the ones of `defaults`
+    pub fn or(&self, defaults: &PushoverOptions) -> PushoverOptions {
+        PushoverOptions {
+            priority: self.priority.or(defaults.priority),
+            sound: self.sound.clone().or_else(|| defaults.sound.clone()),
+            device: self.device.clone().or_else(|| defaults.device.clone()),
+            ttl: self.ttl.or(defaults.ttl),
+            html: self.html.or(defaults.html),
+        }
+    }
 }
 
 #[derive(Debug, Default, PartialEq, Deserialize)]
 /// Pushover configuration
 pub struct PushoverConfig {
     /// User key
+    #[serde(default)]
     pub user: String,
+    /// File to read the user key from, instead of `user`
+    pub user_file: Option<String>,
     /// API token
+    #[serde(default)]
+    pub token: String,
+    /// File to read the API token from, instead of `token`
+    pub token_file: Option<String>,
+    /// Optional url to link to, defaults to the status page
+    pub url: Option<String>,
+    /// Options of every notification
+    #[serde(flatten)]
+    pub options: PushoverOptions,
+    /// Options of the notifications of new failures
+    #[serde(default)]
+    pub failure: PushoverOptions,
+    /// Options of the reminders of ongoing failures
+    #[serde(default)]
+    pub continuous_failure: PushoverOptions,
+    /// Options of the notifications of recoveries
+    #[serde(default)]
+    pub back_from_failure: PushoverOptions,
+}
+
+impl PushoverConfig {
+    /// Resolve the user key and the API token
+    pub async fn resolve_secrets(&mut self, secrets: &mut Secrets) -> Result<(), ConfigError> {
+        resolve_secret(
+            &mut self.user,
+            self.user_file.as_ref(),
+            "pushover user",
+            secrets,
+        )
+        .await?;
+        resolve_secret(
+            &mut self.token,
+            self.token_file.as_ref(),
+            "pushover token",
+            secrets,
+        )
+        .await
+    }
+}
+
+/// Resolve a secret value: read it from `file` if set, otherwise interpolate
+/// the environment variables it references
+async fn resolve_secret(
+    value: &mut String,
+    file: Option<&String>,
+    name: &str,
+    secrets: &mut Secrets,
+) -> Result<(), ConfigError> {
+    if let Some(path) = file {
+        let content = fs::read_to_string(path)
+            .await
+            .context(IoSnafu { path: path.clone() })?;
+        *value = content.trim_end_matches(['\n', '\r']).to_string();
+    } else {
+        *value = interpolate(value).context(SecretSnafu)?.0;
+    }
+    if value.is_empty() {
+        return Err(ConfigError::MissingSecret {
+            name: name.to_string(),
+        });
+    }
+    secrets.add(value);
+    Ok(())
+}
+
+/// Load `env_file` into `env`, then interpolate the environment variables
+/// referenced by the values of `env`
+///
+/// Values referencing a variable are secret, as well as the values of
+/// `env_file` listed in `secret_env` and not overridden by `env`.
+async fn resolve_env(
+    env: &mut Option<HashMap<String, String>>,
+    env_file: Option<&String>,
+    secret_env: &[&String],
+    secrets: &mut Secrets,
+) -> Result<(), ConfigError> {
+    let mut resolved = match env_file {
+        Some(path) => {
+            let content = fs::read_to_string(path)
+                .await
+                .context(IoSnafu { path: path.clone() })?;
+            let file_env = parse_env_file(path, &content).context(SecretSnafu)?;
+            let overridden = |key: &String| env.as_ref().is_some_and(|e| e.contains_key(key));
+            file_env
+                .iter()
+                .filter(|(key, _)| secret_env.contains(key) && !overridden(key))
+                .for_each(|(_, value)| secrets.add(value));
+            Some(file_env)
+        }
+        None => None,
+    };
+    if let Some(values) = env.take() {
+        let resolved = resolved.get_or_insert_with(HashMap::new);
+        for (key, value) in values {
+            let (value, substituted) = interpolate(&value).context(SecretSnafu)?;
+            if substituted {
+                secrets.add(&value);
+            }
+            resolved.insert(key, value);
+        }
+    }
+    *env = resolved;
+    Ok(())
+}
+
+#[derive(Debug, Default, PartialEq, Deserialize)]
+/// Generic webhook configuration
+pub struct WebhookConfig {
+    /// URL to send the notifications to
+    pub url: String,
+    /// HTTP method. Default is POST
+    pub method: Option<String>,
+    /// HTTP headers to set, such as `Authorization`
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    /// Template of the JSON body, where `{{variable}}` is replaced with the
+    /// JSON-escaped value of the variable.
+    /// Default is a JSON object holding every variable
+    pub body: Option<String>,
+}
+
+impl WebhookConfig {
+    /// Interpolate the environment variables referenced by the URL and the
+    /// headers
+    pub async fn resolve_secrets(&mut self, secrets: &mut Secrets) -> Result<(), ConfigError> {
+        for value in std::iter::once(&mut self.url).chain(self.headers.values_mut()) {
+            let (resolved, substituted) = interpolate(value).context(SecretSnafu)?;
+            if substituted {
+                secrets.add(&resolved);
+            }
+            *value = resolved;
+        }
+        Ok(())
+    }
+}
+
+/// Security of the connection to the SMTP server
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SmtpTls {
+    /// Plain text connection, for local relays only
+    None,
+    /// Plain text connection upgraded with STARTTLS, which is required
+    #[default]
+    Starttls,
+    /// TLS from the start, often on port 465
+    Implicit,
+}
+
+/// Where to put the output of a failed command in emails
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SmtpOutput {
+    /// In the body of the email
+    #[default]
+    Body,
+    /// In an `output.txt` attachment
+    Attachment,
+}
+
+#[derive(Debug, Default, PartialEq, Deserialize)]
+/// Email configuration
+pub struct SmtpConfig {
+    /// Host name of the SMTP server
+    pub host: String,
+    /// Port of the SMTP server.
+    /// Default is 25 without TLS, 587 with STARTTLS and 465 with implicit TLS
+    pub port: Option<u16>,
+    /// Security of the connection
+    #[serde(default)]
+    pub tls: SmtpTls,
+    /// User name to authenticate with
+    pub username: Option<String>,
+    /// Password to authenticate with
+    #[serde(default)]
+    pub password: String,
+    /// File to read the password from, instead of `password`
+    pub password_file: Option<String>,
+    /// Sender address, like `Ronde <ronde@example.com>`
+    pub from: String,
+    /// Recipient addresses
+    pub to: Vec<String>,
+    /// Template of the subject. Default is `{{title}}`
+    pub subject: Option<String>,
+    /// Template of the body
+    pub body: Option<String>,
+    /// Where to put the output of the command
+    #[serde(default)]
+    pub output: SmtpOutput,
+}
+
+impl SmtpConfig {
+    /// Resolve the password, if authenticating
+    pub async fn resolve_secrets(&mut self, secrets: &mut Secrets) -> Result<(), ConfigError> {
+        if self.username.is_none() {
+            return Ok(());
+        }
+        resolve_secret(
+            &mut self.password,
+            self.password_file.as_ref(),
+            "smtp password",
+            secrets,
+        )
+        .await
+    }
+
+    /// Get the port to connect to
+    pub fn get_port(&self) -> u16 {
+        self.port.unwrap_or(match self.tls {
+            SmtpTls::None => 25,
+            SmtpTls::Starttls => 587,
+            SmtpTls::Implicit => 465,
+        })
+    }
+}
+
+/// Priority of the notifications of each type, overriding the defaults of
+/// the channel
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+pub struct Priorities {
+    /// Priority of new failures
+    pub failure: Option<u8>,
+    /// Priority of reminders of ongoing failures
+    pub continuous_failure: Option<u8>,
+    /// Priority of recoveries
+    pub back_from_failure: Option<u8>,
+}
+
+#[derive(Debug, Default, PartialEq, Deserialize)]
+/// ntfy configuration
+pub struct NtfyConfig {
+    /// URL of the ntfy server. Default is https://ntfy.sh
+    pub server: Option<String>,
+    /// Topic to publish to
+    pub topic: String,
+    /// Access token, if the topic is protected
+    pub token: Option<String>,
+    /// File to read the access token from, instead of `token`
+    pub token_file: Option<String>,
+    /// Priorities, from 1 (min) to 5 (max).
+    /// Default is 4 for failures, 3 for reminders and 2 for recoveries
+    #[serde(default)]
+    pub priorities: Priorities,
+    /// Tags to add to every notification
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// URL to open when the notification is clicked
+    pub click: Option<String>,
+}
+
+impl NtfyConfig {
+    /// Resolve the access token, if any
+    pub async fn resolve_secrets(&mut self, secrets: &mut Secrets) -> Result<(), ConfigError> {
+        if self.token.is_none() && self.token_file.is_none() {
+            return Ok(());
+        }
+        let token = self.token.get_or_insert_with(String::new);
+        resolve_secret(token, self.token_file.as_ref(), "ntfy token", secrets).await
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Deserialize)]
+/// Gotify configuration
+pub struct GotifyConfig {
+    /// URL of the Gotify server
+    pub server: String,
+    /// Application token
+    #[serde(default)]
     pub token: String,
-    /// Optional url to link to
+    /// File to read the application token from, instead of `token`
+    pub token_file: Option<String>,
+    /// Priorities, from 0 to 10.
+    /// Default is 8 for failures, 5 for reminders and 2 for recoveries
+    #[serde(default)]
+    pub priorities: Priorities,
+    /// Render the message as Markdown, with the output in a code block
+    #[serde(default)]
+    pub markdown: bool,
+    /// URL to open when the notification is clicked
+    pub click: Option<String>,
+}
+
+impl GotifyConfig {
+    /// Resolve the application token
+    pub async fn resolve_secrets(&mut self, secrets: &mut Secrets) -> Result<(), ConfigError> {
+        resolve_secret(
+            &mut self.token,
+            self.token_file.as_ref(),
+            "gotify token",
+            secrets,
+        )
+        .await
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Deserialize)]
+/// Slack or Mattermost incoming webhook configuration
+pub struct SlackConfig {
+    /// URL of the incoming webhook, which is a secret
+    pub webhook_url: String,
+    /// Channel to post to, instead of the default one of the webhook
+    pub channel: Option<String>,
+    /// Name to post as, instead of the default one of the webhook
+    pub username: Option<String>,
+}
+
+#[derive(Debug, Default, PartialEq, Deserialize)]
+/// Discord webhook configuration
+pub struct DiscordConfig {
+    /// URL of the webhook, which is a secret
+    pub webhook_url: String,
+    /// Name to post as, instead of the default one of the webhook
+    pub username: Option<String>,
+}
+
+#[derive(Debug, Default, PartialEq, Deserialize)]
+/// Matrix configuration
+pub struct MatrixConfig {
+    /// URL of the homeserver, like `https://matrix.org`
+    pub homeserver: String,
+    /// ID of the room to send to, like `!abcdef:matrix.org`
+    pub room_id: String,
+    /// Access token of the user sending the messages
+    #[serde(default)]
+    pub access_token: String,
+    /// File to read the access token from, instead of `access_token`
+    pub access_token_file: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+/// Local program to run for each notification
+pub struct ExecConfig {
+    /// Program to run, as a shell command line or as an array
+    pub run: Run,
+    /// Timeout in seconds
+    #[serde(default)]
+    pub timeout: Timeout,
+}
+
+#[derive(Debug, Default, PartialEq, Deserialize)]
+/// PagerDuty Events API v2 configuration
+pub struct PagerDutyConfig {
+    /// Integration key of the service
+    #[serde(default)]
+    pub routing_key: String,
+    /// File to read the integration key from, instead of `routing_key`
+    pub routing_key_file: Option<String>,
+    /// URL to send the events to, default is
+    /// `https://events.pagerduty.com/v2/enqueue`
     pub url: Option<String>,
 }
 
+impl PagerDutyConfig {
+    /// Resolve the integration key
+    pub async fn resolve_secrets(&mut self, secrets: &mut Secrets) -> Result<(), ConfigError> {
+        resolve_secret(
+            &mut self.routing_key,
+            self.routing_key_file.as_ref(),
+            "pagerduty routing_key",
+            secrets,
+        )
+        .await
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Deserialize)]
+/// Opsgenie configuration
+pub struct OpsgenieConfig {
+    /// Key of an API integration
+    #[serde(default)]
+    pub api_key: String,
+    /// File to read the API key from, instead of `api_key`
+    pub api_key_file: Option<String>,
+    /// URL of the API, default is `https://api.opsgenie.com`
+    pub api_url: Option<String>,
+    /// Tags of the alerts
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+impl OpsgenieConfig {
+    /// Resolve the API key
+    pub async fn resolve_secrets(&mut self, secrets: &mut Secrets) -> Result<(), ConfigError> {
+        resolve_secret(
+            &mut self.api_key,
+            self.api_key_file.as_ref(),
+            "opsgenie api_key",
+            secrets,
+        )
+        .await
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Deserialize)]
+/// Prometheus Alertmanager configuration
+pub struct AlertmanagerConfig {
+    /// URL of Alertmanager, like `http://localhost:9093`
+    pub url: String,
+    /// Labels added to every alert
+    #[serde(default)]
+    pub labels: HashMap<String, String>,
+}
+
+/// Telegram chat, by ID or by `@username` for public channels
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum TelegramChatId {
+    /// Numeric ID, negative for groups and channels
+    Id(i64),
+    /// `@username` of a public channel
+    Username(String),
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+/// Telegram bot configuration
+pub struct TelegramConfig {
+    /// Token of the bot, as given by @BotFather
+    #[serde(default)]
+    pub bot_token: String,
+    /// File to read the token of the bot from, instead of `bot_token`
+    pub bot_token_file: Option<String>,
+    /// Chat to send the messages to
+    pub chat_id: TelegramChatId,
+    /// Topic of a forum group to send the messages to
+    pub message_thread_id: Option<i64>,
+    /// URL of the Bot API server, default is `https://api.telegram.org`
+    pub api_url: Option<String>,
+}
+
+impl TelegramConfig {
+    /// Resolve the token of the bot
+    pub async fn resolve_secrets(&mut self, secrets: &mut Secrets) -> Result<(), ConfigError> {
+        resolve_secret(
+            &mut self.bot_token,
+            self.bot_token_file.as_ref(),
+            "telegram bot_token",
+            secrets,
+        )
+        .await
+    }
+}
+
+/// Notification channel, selected by its `type`
+#[derive(Debug, PartialEq, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum ChannelConfig {
+    /// Pushover
+    Pushover(PushoverConfig),
+    /// Generic webhook
+    Webhook(WebhookConfig),
+    /// Email
+    Smtp(SmtpConfig),
+    /// ntfy
+    Ntfy(NtfyConfig),
+    /// Gotify
+    Gotify(GotifyConfig),
+    /// Slack or Mattermost incoming webhook
+    #[serde(alias = "mattermost")]
+    Slack(SlackConfig),
+    /// Discord webhook
+    Discord(DiscordConfig),
+    /// Matrix room
+    Matrix(MatrixConfig),
+    /// Telegram bot
+    Telegram(TelegramConfig),
+    /// Local program
+    Exec(ExecConfig),
+    /// PagerDuty Events API v2
+    PagerDuty(PagerDutyConfig),
+    /// Opsgenie
+    Opsgenie(OpsgenieConfig),
+    /// Prometheus Alertmanager
+    Alertmanager(AlertmanagerConfig),
+}
+
+impl ChannelConfig {
+    /// Resolve the credentials of the channel
+    pub async fn resolve_secrets(&mut self, secrets: &mut Secrets) -> Result<(), ConfigError> {
+        match self {
+            ChannelConfig::Pushover(pushover) => pushover.resolve_secrets(secrets).await,
+            ChannelConfig::Webhook(webhook) => webhook.resolve_secrets(secrets).await,
+            ChannelConfig::Smtp(smtp) => smtp.resolve_secrets(secrets).await,
+            ChannelConfig::Ntfy(ntfy) => ntfy.resolve_secrets(secrets).await,
+            ChannelConfig::Gotify(gotify) => gotify.resolve_secrets(secrets).await,
+            ChannelConfig::Slack(slack) => {
+                resolve_secret(&mut slack.webhook_url, None, "slack webhook_url", secrets).await
+            }
+            ChannelConfig::Discord(discord) => {
+                let name = "discord webhook_url";
+                resolve_secret(&mut discord.webhook_url, None, name, secrets).await
+            }
+            ChannelConfig::Matrix(matrix) => {
+                let file = matrix.access_token_file.as_ref();
+                resolve_secret(
+                    &mut matrix.access_token,
+                    file,
+                    "matrix access_token",
+                    secrets,
+                )
+                .await
+            }
+            ChannelConfig::Telegram(telegram) => telegram.resolve_secrets(secrets).await,
+            ChannelConfig::Exec(_) => Ok(()),
+            ChannelConfig::PagerDuty(pagerduty) => pagerduty.resolve_secrets(secrets).await,
+            ChannelConfig::Opsgenie(opsgenie) => opsgenie.resolve_secrets(secrets).await,
+            ChannelConfig::Alertmanager(_) => Ok(()),
+        }
+    }
+}
+
 #[derive(Debug, Default, PartialEq, Deserialize)]
 /// Notification configuration
 pub struct NotificationConfig {
-    /// Pushover configuration
+    /// Pushover configuration, same as a channel of type `pushover`
     pub pushover: Option<PushoverConfig>,
+    /// Channels to send every notification to
+    #[serde(default)]
+    pub channels: Vec<ChannelConfig>,
+    /// URL of the status page, linked from the notifications of the chat
+    /// channels and of Pushover
+    pub status_page: Option<String>,
     /// Notify on success after failure
     #[serde(default)]
     pub notify_on_success_after_failure: bool,
@@ -129,6 +1090,91 @@ pub struct NotificationConfig {
     pub minutes_between_continuous_failure_notification: i64,
 }
 
+/// Which command outputs to keep in the history
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputRetention {
+    /// Keep the output of every entry
+    #[default]
+    All,
+    /// Keep the output of failures and of the most recent success only
+    FailuresAndLatest,
+}
+
+/// Format of the logs
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// Human readable lines on stderr
+    #[default]
+    Text,
+    /// One JSON object per line on stderr
+    Json,
+    /// Messages sent to the local syslog daemon through /dev/log
+    Syslog,
+}
+
+/// Minimum level of the logs
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    /// Errors only
+    Error,
+    /// Warnings, such as failed commands, and errors
+    #[default]
+    Warn,
+    /// Commands run, notifications sent, files written...
+    Info,
+    /// Details
+    Debug,
+    /// Everything
+    Trace,
+}
+
+/// Logging configuration
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+pub struct LoggingConfig {
+    /// Format of the logs
+    #[serde(default)]
+    pub format: LogFormat,
+    /// Minimum level of the logs, raised by each `-v` flag.
+    /// Default is warn
+    #[serde(default)]
+    pub level: LogLevel,
+}
+
+/// Resolve `user` and `group` into `uid` and `gid`
+///
+/// When only `user` is set, `gid` is the primary group of the user.
+fn resolve_identity(
+    context: &str,
+    user: &Option<String>,
+    group: &Option<String>,
+    uid: &mut Option<u32>,
+    gid: &mut Option<u32>,
+) -> Result<(), ConfigError> {
+    let ambiguous = |name: &str, id: &str| ConfigError::AmbiguousIdentity {
+        context: context.to_string(),
+        name: name.to_string(),
+        id: id.to_string(),
+    };
+    if let Some(ref group) = group {
+        if gid.is_some() {
+            return Err(ambiguous("group", "gid"));
+        }
+        *gid = Some(group_from_name(group).context(UserSnafu { context })?);
+    }
+    if let Some(ref user) = user {
+        if uid.is_some() {
+            return Err(ambiguous("user", "uid"));
+        }
+        let user = User::from_name(user).context(UserSnafu { context })?;
+        *uid = Some(user.uid);
+        gid.get_or_insert(user.gid);
+    }
+    Ok(())
+}
+
 /// Error type for configuration
 #[derive(Debug, Snafu)]
 pub enum ConfigError {
@@ -144,9 +1190,226 @@ pub enum ConfigError {
         source: toml::de::Error,
         path: String,
     },
+    /// SerdeYaml Error
+    #[snafu(display("Unable to parse YAML in {}: {}", path, source))]
+    SerdeYamlError {
+        source: serde_yaml::Error,
+        path: String,
+    },
+    /// SerdeJson Error
+    #[snafu(display("Unable to parse JSON in {}: {}", path, source))]
+    SerdeJsonError {
+        source: serde_json::Error,
+        path: String,
+    },
+    /// Template Error
+    #[snafu(display("Template Error at {}: {}", location, source))]
+    TemplateError {
+        source: TemplateError,
+        location: String,
+    },
+    /// Matrix Error
+    #[snafu(display("Matrix Error at {}: {}", location, source))]
+    MatrixError {
+        source: MatrixError,
+        location: String,
+    },
+    /// Template name is not unique
+    #[snafu(display("Template {} defined in {} is already defined", name, path))]
+    NotUniqueTemplateName { name: String, path: String },
+    /// Invalid command
+    #[snafu(display("Invalid command at {}: {}", location, source))]
+    InvalidCommand {
+        source: toml::de::Error,
+        location: String,
+    },
+    /// Unexpected key in an included file
+    #[snafu(display("Unexpected key {} in included file {}", key, path))]
+    UnexpectedKey { key: String, path: String },
+    /// Invalid include pattern
+    #[snafu(display("Invalid include pattern {}: {}", pattern, source))]
+    InvalidInclude {
+        source: glob::PatternError,
+        pattern: String,
+    },
     /// Command name is not unique
-    #[snafu(display("Command name {} is not unique", cmd))]
-    NotUniqueCommandName { cmd: String },
+    #[snafu(display(
+        "Command name {} defined at {} is not unique, it is already defined at {}",
+        cmd,
+        duplicate,
+        first
+    ))]
+    NotUniqueCommandName {
+        cmd: String,
+        first: String,
+        duplicate: String,
+    },
+    /// Command does not properly define what to run
+    #[snafu(display("Command {}: {}", cmd, reason))]
+    InvalidRun { cmd: String, reason: String },
+    /// Secret Error
+    #[snafu(display("Secret Error: {}", source))]
+    SecretError { source: SecretError },
+    /// Secret is empty
+    #[snafu(display("{} is not set", name))]
+    MissingSecret { name: String },
+    /// Command has both stdin and stdin_file
+    #[snafu(display("Command {}: both stdin and stdin_file are set", cmd))]
+    InvalidStdin { cmd: String },
+    /// User or group cannot be resolved
+    #[snafu(display("{}: {}", context, source))]
+    UserError { source: UserError, context: String },
+    /// Both a name and an ID are given for the user or the group
+    #[snafu(display("{}: both {} and {} are set", context, name, id))]
+    AmbiguousIdentity {
+        context: String,
+        name: String,
+        id: String,
+    },
+}
+
+/// Commands and templates of a configuration file, before resolving the
+/// templates
+///
+/// `T` is `toml::Spanned<toml::Table>` for TOML files, to know where the
+/// commands are defined.
+#[derive(Debug, Default, Deserialize)]
+struct RawCommands<T> {
+    /// Commands
+    #[serde(default = "Vec::new")]
+    commands: Vec<T>,
+    /// Command templates
+    #[serde(default)]
+    templates: HashMap<String, toml::Table>,
+    /// Matrices
+    #[serde(default = "Vec::new")]
+    matrix: Vec<T>,
+}
+
+/// Attach the location of a table to it
+fn locate(
+    path: &str,
+    contents: &str,
+    table: toml::Spanned<toml::Table>,
+) -> (toml::Table, Location) {
+    let line = contents[..table.span().start].matches('\n').count() + 1;
+    let location = Location {
+        file: path.to_string(),
+        line: Some(line),
+    };
+    (table.into_inner(), location)
+}
+
+/// Content of a configuration file
+struct ConfigFile {
+    /// Everything but the commands, the templates and the matrices
+    table: toml::Table,
+    /// Commands, along with where they are defined
+    commands: Vec<(toml::Table, Location)>,
+    /// Command templates
+    templates: HashMap<String, toml::Table>,
+    /// Matrices, along with where they are defined
+    matrices: Vec<(toml::Table, Location)>,
+}
+
+/// Expand a `[[matrix]]` section into commands
+async fn expand_matrix(
+    table: toml::Table,
+    location: Location,
+    templates: &HashMap<String, toml::Table>,
+) -> Result<Vec<(toml::Table, Location)>, ConfigError> {
+    let matrix: Matrix = toml::Value::Table(table)
+        .try_into()
+        .context(InvalidCommandSnafu {
+            location: location.to_string(),
+        })?;
+    let targets = match (matrix.targets, matrix.targets_file) {
+        (Some(targets), None) => targets.into_iter().map(Into::into).collect(),
+        (None, Some(targets_file)) => {
+            let base = Path::new(&location.file).parent().unwrap_or(Path::new(""));
+            let path = base.join(&targets_file).to_string_lossy().to_string();
+            let content = fs::read_to_string(&path)
+                .await
+                .context(IoSnafu { path: path.clone() })?;
+            let format = matrix
+                .format
+                .unwrap_or_else(|| TargetsFormat::from_path(&path));
+            matrix::parse_targets(&content, format).context(MatrixSnafu {
+                location: location.to_string(),
+            })?
+        }
+        _ => {
+            return Err(ConfigError::MatrixError {
+                source: MatrixError::InvalidTargets,
+                location: location.to_string(),
+            })
+        }
+    };
+    let commands = matrix::expand(matrix.command, targets, templates).context(MatrixSnafu {
+        location: location.to_string(),
+    })?;
+    Ok(commands
+        .into_iter()
+        .map(|c| (c, location.clone()))
+        .collect())
+}
+
+impl ConfigFile {
+    /// Read and parse a configuration file
+    async fn read(path: &str, format: ConfigFormat) -> Result<Self, ConfigError> {
+        let contents = fs::read_to_string(path).await.context(IoSnafu {
+            path: path.to_string(),
+        })?;
+        let mut table = format.parse(path, &contents)?;
+        let mut raw = toml::Table::new();
+        for key in ["commands", "templates", "matrix"] {
+            if let Some(value) = table.remove(key) {
+                raw.insert(key.to_string(), value);
+            }
+        }
+        if format == ConfigFormat::Toml {
+            let raw: RawCommands<toml::Spanned<toml::Table>> =
+                toml::from_str(&contents).context(SerdeTomlSnafu {
+                    path: path.to_string(),
+                })?;
+            return Ok(ConfigFile {
+                table,
+                commands: raw
+                    .commands
+                    .into_iter()
+                    .map(|c| locate(path, &contents, c))
+                    .collect(),
+                matrices: raw
+                    .matrix
+                    .into_iter()
+                    .map(|m| locate(path, &contents, m))
+                    .collect(),
+                templates: raw.templates,
+            });
+        }
+        let raw: RawCommands<toml::Table> =
+            toml::Value::Table(raw).try_into().context(SerdeTomlSnafu {
+                path: path.to_string(),
+            })?;
+        let location = Location {
+            file: path.to_string(),
+            line: None,
+        };
+        Ok(ConfigFile {
+            table,
+            commands: raw
+                .commands
+                .into_iter()
+                .map(|c| (c, location.clone()))
+                .collect(),
+            matrices: raw
+                .matrix
+                .into_iter()
+                .map(|m| (m, location.clone()))
+                .collect(),
+            templates: raw.templates,
+        })
+    }
 }
 
 #[derive(Debug, Default, PartialEq, Deserialize)]
@@ -160,44 +1423,262 @@ pub struct Config {
     pub uid: Option<u32>,
     /// GID to send notifications and write files
     pub gid: Option<u32>,
+    /// Name of the user to send notifications and write files, instead of
+    /// `uid`
+    pub user: Option<String>,
+    /// Name of the group to send notifications and write files, instead of
+    /// `gid`. Defaults to the primary group of `user`
+    pub group: Option<String>,
     /// Output directory
     /// This is where the HTML file will be written
     pub output_dir: String,
     /// Notification configuration
     pub notifications: Option<NotificationConfig>,
     /// List of commands to run
+    #[serde(default)]
     pub commands: Vec<CommandConfig>,
+    /// Glob patterns of files to read more commands from.
+    /// Relative patterns are relative to the directory of the main file.
+    #[serde(default)]
+    pub include: Vec<String>,
     /// Default settings for running commands
     #[serde(default)]
     pub default_env: DefaultRunnerEnv,
+    /// Which command outputs to keep in the history
+    #[serde(default)]
+    pub output_retention: OutputRetention,
+    /// Logging configuration
+    #[serde(default)]
+    pub logging: LoggingConfig,
+    /// Secret values found while loading the configuration
+    #[serde(skip)]
+    pub secrets: Secrets,
 }
 
 impl Config {
-    /// Load configuration from TOML files
-    pub async fn load(toml_file: &str) -> Result<Self, ConfigError> {
-        let file_contents = fs::read_to_string(toml_file).await.context(IoSnafu {
-            path: toml_file.to_string(),
-        })?;
-        let config: Config = toml::from_str(&file_contents).context(SerdeTomlSnafu {
-            path: toml_file.to_string(),
-        })?;
-        config.check_unique_command_names()?;
+    /// Load configuration from files, whose format is guessed from their
+    /// extension
+    pub async fn load(config_file: &str) -> Result<Self, ConfigError> {
+        Self::load_with_format(config_file, ConfigFormat::from_path(config_file)).await
+    }
+
+    /// Read a configuration file and the files it includes, and expand
+    /// its templates and matrices
+    ///
+    /// Users, groups and secrets are not resolved yet.
+    pub async fn parse_with_format(
+        config_file: &str,
+        format: ConfigFormat,
+    ) -> Result<Self, ConfigError> {
+        let main = ConfigFile::read(config_file, format).await?;
+        let mut config: Config =
+            toml::Value::Table(main.table)
+                .try_into()
+                .context(SerdeTomlSnafu {
+                    path: config_file.to_string(),
+                })?;
+        let mut commands = main.commands;
+        let mut templates = main.templates;
+        let mut matrices = main.matrices;
+        for path in config.included_files(config_file)? {
+            let included = ConfigFile::read(&path, ConfigFormat::from_path(&path)).await?;
+            if let Some(key) = included.table.keys().next() {
+                return Err(ConfigError::UnexpectedKey {
+                    key: key.clone(),
+                    path,
+                });
+            }
+            for (name, template) in included.templates {
+                if templates.contains_key(&name) {
+                    return Err(ConfigError::NotUniqueTemplateName { name, path });
+                }
+                templates.insert(name, template);
+            }
+            commands.extend(included.commands);
+            matrices.extend(included.matrices);
+        }
+        for (table, location) in matrices {
+            commands.extend(expand_matrix(table, location, &templates).await?);
+        }
+        config.resolve_commands(commands, &templates)?;
         Ok(config)
     }
 
-    /// Check that all the command names are unique
-    pub fn check_unique_command_names(&self) -> Result<(), ConfigError> {
-        let mut names = HashSet::new();
+    /// Load configuration from files, the main one being in the given format
+    ///
+    /// The format of the included files is guessed from their extension.
+    pub async fn load_with_format(
+        config_file: &str,
+        format: ConfigFormat,
+    ) -> Result<Self, ConfigError> {
+        let mut config = Self::parse_with_format(config_file, format).await?;
+        match config.validate().await.into_iter().next() {
+            Some((_, e)) => Err(e),
+            None => Ok(config),
+        }
+    }
+
+    /// Resolve the users, groups and secrets, and validate the commands
+    ///
+    /// Every problem found is returned, not only the first one, along with
+    /// the location of the command it is about.
+    pub async fn validate(&mut self) -> Vec<(Option<Location>, ConfigError)> {
+        let mut errors = Vec::new();
+        self.resolve_users(&mut errors);
+        self.resolve_secrets(&mut errors).await;
+        errors.extend(self.duplicate_command_names());
         for command in &self.commands {
-            if names.contains(&command.name) {
-                return Err(ConfigError::NotUniqueCommandName {
-                    cmd: command.name.clone(),
-                });
+            let errors_of_command = [
+                command.check_run(&self.default_env).err(),
+                command.check_stdin().err(),
+            ];
+            for e in errors_of_command.into_iter().flatten() {
+                errors.push((command.location.clone(), e));
             }
-            names.insert(command.name.clone());
+        }
+        errors
+    }
+
+    /// Get the paths of the included files, in order
+    fn included_files(&self, config_file: &str) -> Result<Vec<String>, ConfigError> {
+        let base = Path::new(config_file).parent().unwrap_or(Path::new(""));
+        let mut files = Vec::new();
+        for pattern in &self.include {
+            let full_pattern = base.join(pattern);
+            let full_pattern = full_pattern.to_string_lossy();
+            let paths = glob::glob(&full_pattern).context(InvalidIncludeSnafu {
+                pattern: pattern.clone(),
+            })?;
+            let mut paths: Vec<String> = paths
+                .filter_map(Result::ok)
+                .map(|p| p.to_string_lossy().to_string())
+                .collect();
+            paths.sort();
+            files.extend(paths);
+        }
+        Ok(files)
+    }
+
+    /// Resolve the templates of the commands and append them to the
+    /// configuration
+    fn resolve_commands(
+        &mut self,
+        commands: Vec<(toml::Table, Location)>,
+        templates: &HashMap<String, toml::Table>,
+    ) -> Result<(), ConfigError> {
+        for (table, location) in commands {
+            let table = template::resolve(table, templates).context(TemplateSnafu {
+                location: location.to_string(),
+            })?;
+            let mut command: CommandConfig =
+                toml::Value::Table(table)
+                    .try_into()
+                    .context(InvalidCommandSnafu {
+                        location: location.to_string(),
+                    })?;
+            command.location = Some(location);
+            self.commands.push(command);
         }
         Ok(())
     }
+
+    /// Resolve the names of users and groups into UIDs and GIDs
+    pub fn resolve_users(&mut self, errors: &mut Vec<(Option<Location>, ConfigError)>) {
+        let config = resolve_identity(
+            "config",
+            &self.user,
+            &self.group,
+            &mut self.uid,
+            &mut self.gid,
+        );
+        errors.extend(config.err().map(|e| (None, e)));
+        let defaults = &mut self.default_env;
+        let defaults = resolve_identity(
+            "default_env",
+            &defaults.user,
+            &defaults.group,
+            &mut defaults.uid,
+            &mut defaults.gid,
+        );
+        errors.extend(defaults.err().map(|e| (None, e)));
+        for command in self.commands.iter_mut() {
+            let resolved = resolve_identity(
+                &format!("Command {}", command.name),
+                &command.user,
+                &command.group,
+                &mut command.uid,
+                &mut command.gid,
+            );
+            errors.extend(resolved.err().map(|e| (command.location.clone(), e)));
+        }
+    }
+
+    /// Read the files holding secrets and interpolate the environment
+    /// variables referenced by the notification credentials and by the
+    /// environment of the commands
+    pub async fn resolve_secrets(&mut self, errors: &mut Vec<(Option<Location>, ConfigError)>) {
+        let mut secrets = Secrets::default();
+        if let Some(ref mut notifications) = self.notifications {
+            if let Some(ref mut pushover) = notifications.pushover {
+                let resolved = pushover.resolve_secrets(&mut secrets).await;
+                errors.extend(resolved.err().map(|e| (None, e)));
+            }
+            for channel in notifications.channels.iter_mut() {
+                let resolved = channel.resolve_secrets(&mut secrets).await;
+                errors.extend(resolved.err().map(|e| (None, e)));
+            }
+        }
+        let defaults = &mut self.default_env;
+        let default_secret_env: Vec<&String> = defaults.secret_env.iter().collect();
+        let resolved = resolve_env(
+            &mut defaults.env,
+            defaults.env_file.as_ref(),
+            &default_secret_env,
+            &mut secrets,
+        )
+        .await;
+        errors.extend(resolved.err().map(|e| (None, e)));
+        for command in self.commands.iter_mut() {
+            // Variables designated as secret by default are secret in every
+            // command
+            let secret_env: Vec<&String> = command
+                .secret_env
+                .iter()
+                .chain(default_secret_env.iter().copied())
+                .collect();
+            let resolved = resolve_env(
+                &mut command.env,
+                command.env_file.as_ref(),
+                &secret_env,
+                &mut secrets,
+            )
+            .await;
+            errors.extend(resolved.err().map(|e| (command.location.clone(), e)));
+        }
+        self.secrets = secrets;
+    }
+
+    /// Get an error for each command whose name is already used
+    fn duplicate_command_names(&self) -> Vec<(Option<Location>, ConfigError)> {
+        let mut errors = Vec::new();
+        let mut names: HashMap<&String, &CommandConfig> = HashMap::new();
+        for command in &self.commands {
+            match names.get(&command.name) {
+                Some(first) => errors.push((
+                    command.location.clone(),
+                    ConfigError::NotUniqueCommandName {
+                        cmd: command.name.clone(),
+                        first: first.location_str(),
+                        duplicate: command.location_str(),
+                    },
+                )),
+                None => {
+                    names.insert(&command.name, command);
+                }
+            }
+        }
+        errors
+    }
 }
 
 #[cfg(test)]
@@ -215,6 +1696,7 @@ mod tests {
 output_dir = "/var/www/html"
 history_file= "/var/lib/ronde/history"
 name = "Ronde"
+output_retention = "failures_and_latest"
 [notifications]
     notify_on_success_after_failure = true
     minutes_between_continuous_failure_notification = 120
@@ -226,8 +1708,14 @@ name = "Ronde"
     gid = 12340
     clear_env = true
     cwd = "/"
+    max_output_bytes = 65536
+    no_new_privs = true
+    unshare = ["network"]
     [default_env.env]
     KEY1 = "DefaultValue1"
+    [default_env.rlimits]
+    nofile = 1024
+    cpu = 30
 [[commands]]
     name = "test"
     timeout = 10
@@ -241,11 +1729,29 @@ name = "Ronde"
     env.KEY1 = "Value1"
     env.KEY2 = "Value2"
     cwd = "/tmp"
+    rlimits.address_space = 1073741824
+    rlimits.cpu = 5
+    chroot = "/srv/jail"
+    unshare = ["network", "mount", "pid"]
+[[commands]]
+    name = "ping argv"
+    run = ["ping", "-c", "4", "local host"]
+[[commands]]
+    name = "script"
+    interpreter = "python3"
+    script = "print('ok')"
+    stdin = "input"
 "#
         )
         .unwrap();
         let cfg_file = file.path().to_str().unwrap().to_string();
-        let config = Config::load(&cfg_file).await.unwrap();
+        let mut config = Config::load(&cfg_file).await.unwrap();
+        let lines: Vec<usize> = config
+            .commands
+            .iter_mut()
+            .map(|c| c.location.take().unwrap().line.unwrap())
+            .collect();
+        assert_eq!(lines, vec![25, 31, 42, 45]);
         assert_eq!(
             config,
             Config {
@@ -266,7 +1772,7 @@ name = "Ronde"
                     CommandConfig {
                         name: "test".to_string(),
                         timeout: Timeout(10),
-                        run: "echo \"test\"".to_string(),
+                        run: Some("echo \"test\"".into()),
                         uid: Some(1000),
                         gid: Some(1234),
                         ..Default::default()
@@ -274,13 +1780,37 @@ name = "Ronde"
                     CommandConfig {
                         name: "ping localhost".to_string(),
                         timeout: Timeout(60),
-                        run: "ping -c 4 localhost".to_string(),
+                        run: Some("ping -c 4 localhost".into()),
                         clear_env: true,
                         env: Some(HashMap::from([
                             ("KEY1".to_string(), "Value1".to_string()),
                             ("KEY2".to_string(), "Value2".to_string())
                         ])),
                         cwd: Some("/tmp".to_string()),
+                        rlimits: Some(ResourceLimits {
+                            address_space: Some(1073741824),
+                            cpu: Some(5),
+                            ..Default::default()
+                        }),
+                        chroot: Some("/srv/jail".to_string()),
+                        unshare: Some(vec![Namespace::Network, Namespace::Mount, Namespace::Pid]),
+                        ..Default::default()
+                    },
+                    CommandConfig {
+                        name: "ping argv".to_string(),
+                        run: Some(Run::Argv(vec![
+                            "ping".to_string(),
+                            "-c".to_string(),
+                            "4".to_string(),
+                            "local host".to_string()
+                        ])),
+                        ..Default::default()
+                    },
+                    CommandConfig {
+                        name: "script".to_string(),
+                        interpreter: Some("python3".to_string()),
+                        script: Some("print('ok')".to_string()),
+                        stdin: Some("input".to_string()),
                         ..Default::default()
                     }
                 ],
@@ -293,10 +1823,545 @@ name = "Ronde"
                         "DefaultValue1".to_string()
                     )])),
                     cwd: Some("/".to_string()),
+                    max_output_bytes: Some(65536),
+                    rlimits: Some(ResourceLimits {
+                        nofile: Some(1024),
+                        cpu: Some(30),
+                        ..Default::default()
+                    }),
+                    no_new_privs: Some(true),
+                    unshare: Some(vec![Namespace::Network]),
+                    ..Default::default()
                 },
+                output_retention: OutputRetention::FailuresAndLatest,
+                secrets: {
+                    let mut secrets = Secrets::default();
+                    secrets.add("user123");
+                    secrets.add("token123");
+                    secrets
+                },
+                ..Default::default()
+            }
+        );
+        let defaults = &config.default_env;
+        assert_eq!(
+            config.commands[1].get_rlimits(defaults),
+            ResourceLimits {
+                address_space: Some(1073741824),
+                nofile: Some(1024),
+                cpu: Some(5),
                 ..Default::default()
             }
         );
+        assert!(config.commands[1].get_no_new_privs(defaults));
+        assert_eq!(
+            config.commands[0].get_unshare(defaults),
+            vec![Namespace::Network]
+        );
+        assert_eq!(config.commands[0].get_chroot(defaults), None);
+        assert_eq!(config.commands[0].command_line(), "echo \"test\"");
+        assert_eq!(config.commands[2].command_line(), "ping -c 4 'local host'");
+        assert_eq!(config.commands[3].command_line(), "#!python3\nprint('ok')");
+    }
+
+    #[test]
+    fn test_check_run() {
+        let mut defaults = DefaultRunnerEnv::default();
+        let mut command = CommandConfig {
+            name: "test".to_string(),
+            ..Default::default()
+        };
+        assert!(command.check_run(&defaults).is_err());
+        command.run = Some(Run::Argv(vec![]));
+        assert!(command.check_run(&defaults).is_err());
+        command.run = Some(Run::Argv(vec!["true".to_string()]));
+        assert!(command.check_run(&defaults).is_ok());
+        command.interpreter = Some("python3".to_string());
+        assert!(command.check_run(&defaults).is_err());
+        command.script = Some("print('ok')".to_string());
+        assert!(command.check_run(&defaults).is_err());
+        command.run = None;
+        assert!(command.check_run(&defaults).is_ok());
+        defaults.chroot = Some("/srv/ronde".to_string());
+        assert!(command.check_run(&defaults).is_err());
+    }
+
+    #[test]
+    fn test_check_stdin() {
+        let mut command = CommandConfig {
+            name: "test".to_string(),
+            run: Some("cat".into()),
+            ..Default::default()
+        };
+        assert!(command.check_stdin().is_ok());
+        command.stdin = Some("input".to_string());
+        assert!(command.check_stdin().is_ok());
+        command.stdin_file = Some("/etc/hostname".to_string());
+        assert!(command.check_stdin().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_load_secrets() {
+        std::env::set_var("RONDE_TEST_DB_PASSWORD", "db-p4ssw0rd");
+        let mut token_file = NamedTempFile::new().unwrap();
+        writeln!(token_file, "t0k3n-from-file").unwrap();
+        let mut env_file = NamedTempFile::new().unwrap();
+        writeln!(
+            env_file,
+            "API_KEY=k3y-from-env-file\nDB_USER=ronde\nDB_HOST=db.example.com"
+        )
+        .unwrap();
+        let mut file = NamedTempFile::new().unwrap();
+        write!(
+            file,
+            r#"
+output_dir = "/var/www/html"
+history_file= "/var/lib/ronde/history"
+name = "Ronde"
+[notifications.pushover]
+    user = "user123"
+    token_file = "{}"
+[[commands]]
+    name = "db"
+    run = "check_db"
+    env_file = "{}"
+    secret_env = ["API_KEY", "DB_USER"]
+    env.DB_USER = "admin"
+    env.DB_PASSWORD = "${{RONDE_TEST_DB_PASSWORD}}"
+"#,
+            token_file.path().display(),
+            env_file.path().display()
+        )
+        .unwrap();
+        let cfg_file = file.path().to_str().unwrap().to_string();
+        let config = Config::load(&cfg_file).await.unwrap();
+        let pushover = config.notifications.unwrap().pushover.unwrap();
+        assert_eq!(pushover.token, "t0k3n-from-file");
+        assert_eq!(
+            config.commands[0].env,
+            Some(HashMap::from([
+                ("API_KEY".to_string(), "k3y-from-env-file".to_string()),
+                ("DB_HOST".to_string(), "db.example.com".to_string()),
+                ("DB_USER".to_string(), "admin".to_string()),
+                ("DB_PASSWORD".to_string(), "db-p4ssw0rd".to_string()),
+            ]))
+        );
+        assert_eq!(
+            config
+                .secrets
+                .redact("user123 t0k3n-from-file k3y-from-env-file db-p4ssw0rd admin"),
+            "******** ******** ******** ******** admin"
+        );
+        // Only the values designated as secret and not overridden are
+        // redacted
+        assert_eq!(
+            config.secrets.redact("ronde db.example.com"),
+            "ronde db.example.com"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_load_includes() {
+        let dir = tempfile::tempdir().unwrap();
+        let conf_d = dir.path().join("conf.d");
+        std::fs::create_dir(&conf_d).unwrap();
+        std::fs::write(
+            dir.path().join("config.toml"),
+            r#"
+output_dir = "/var/www/html"
+history_file= "/var/lib/ronde/history"
+name = "Ronde"
+include = ["conf.d/*.toml"]
+[[commands]]
+    name = "main"
+    run = "true"
+"#,
+        )
+        .unwrap();
+        std::fs::write(
+            conf_d.join("b.toml"),
+            r#"
+[[commands]]
+    name = "b"
+    run = "true"
+"#,
+        )
+        .unwrap();
+        std::fs::write(
+            conf_d.join("a.toml"),
+            r#"[[commands]]
+    name = "a1"
+    run = "true"
+[[commands]]
+    name = "a2"
+    run = "true"
+"#,
+        )
+        .unwrap();
+        let main = dir.path().join("config.toml");
+        let config = Config::load(main.to_str().unwrap()).await.unwrap();
+        let names: Vec<&str> = config.commands.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, vec!["main", "a1", "a2", "b"]);
+        assert_eq!(
+            config.commands[2].location,
+            Some(Location {
+                file: conf_d.join("a.toml").to_str().unwrap().to_string(),
+                line: Some(4),
+            })
+        );
+
+        // Duplicates across files are reported with their location
+        std::fs::write(
+            conf_d.join("c.toml"),
+            r#"
+[[commands]]
+    name = "main"
+    run = "false"
+"#,
+        )
+        .unwrap();
+        let err = Config::load(main.to_str().unwrap()).await.unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "Command name main defined at {}:2 is not unique, it is already defined at {}:6",
+                conf_d.join("c.toml").display(),
+                main.display()
+            )
+        );
+
+        // Only commands and templates can be defined in included files
+        std::fs::write(conf_d.join("c.toml"), "name = \"other\"\n").unwrap();
+        assert!(matches!(
+            Config::load(main.to_str().unwrap()).await,
+            Err(ConfigError::UnexpectedKey { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_load_templates() {
+        let dir = tempfile::tempdir().unwrap();
+        let conf_d = dir.path().join("conf.d");
+        std::fs::create_dir(&conf_d).unwrap();
+        std::fs::write(
+            dir.path().join("config.toml"),
+            r#"
+output_dir = "/var/www/html"
+history_file= "/var/lib/ronde/history"
+name = "Ronde"
+include = ["conf.d/*.toml"]
+[templates.ping]
+    name = "ping {{host}}"
+    run = ["ping", "-c", "4", "{{host}}"]
+    timeout = 5
+    tags = ["network"]
+    notifications.minutes_between_continuous_failure_notification = 30
+[[commands]]
+    template = "ping"
+    vars.host = "example.com"
+[[commands]]
+    template = "ping"
+    name = "ping gateway"
+    vars.host = "192.168.0.1"
+    notifications.enabled = false
+"#,
+        )
+        .unwrap();
+        std::fs::write(
+            conf_d.join("web.toml"),
+            r#"
+[templates.http]
+    template = "ping"
+    run = "curl -sf https://{{host}}/"
+    tags = ["web"]
+[[commands]]
+    template = "http"
+    name = "http {{host}}"
+    vars.host = "example.org"
+"#,
+        )
+        .unwrap();
+        let main = dir.path().join("config.toml");
+        let config = Config::load(main.to_str().unwrap()).await.unwrap();
+        let names: Vec<&str> = config.commands.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec!["ping example.com", "ping gateway", "http example.org"]
+        );
+        let ping = &config.commands[0];
+        assert_eq!(
+            ping.run,
+            Some(Run::Argv(vec![
+                "ping".to_string(),
+                "-c".to_string(),
+                "4".to_string(),
+                "example.com".to_string()
+            ]))
+        );
+        assert_eq!(ping.timeout, Timeout(5));
+        assert_eq!(ping.tags, vec!["network".to_string()]);
+        assert!(ping.notifications_enabled());
+        assert_eq!(
+            ping.get_minutes_between_continuous_failure_notification(
+                &NotificationConfig::default()
+            ),
+            30
+        );
+        assert!(!config.commands[1].notifications_enabled());
+        let http = &config.commands[2];
+        assert_eq!(http.run, Some("curl -sf https://example.org/".into()));
+        assert_eq!(http.timeout, Timeout(5));
+        assert_eq!(http.tags, vec!["web".to_string()]);
+        assert_eq!(http.location.as_ref().unwrap().line, Some(6));
+
+        // Unknown templates are reported with the command location
+        std::fs::write(
+            conf_d.join("web.toml"),
+            "[[commands]]\n    template = \"unknown\"\n",
+        )
+        .unwrap();
+        let err = Config::load(main.to_str().unwrap()).await.unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "Template Error at {}:1: Unknown template unknown",
+                conf_d.join("web.toml").display()
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn test_load_matrix() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("config.toml"),
+            r#"
+output_dir = "/var/www/html"
+history_file= "/var/lib/ronde/history"
+name = "Ronde"
+[templates.ping]
+    run = ["ping", "-c", "4", "{{target}}"]
+    timeout = 5
+[[commands]]
+    name = "true"
+    run = "true"
+[[matrix]]
+    template = "ping"
+    targets = ["a.example.com", "b.example.com"]
+[[matrix]]
+    template = "ping"
+    targets_file = "hosts"
+    tags = ["{{group}}"]
+"#,
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("hosts"),
+            "[web]\nweb1 group=web\nweb2 group=web\n",
+        )
+        .unwrap();
+        let main = dir.path().join("config.toml");
+        let config = Config::load(main.to_str().unwrap()).await.unwrap();
+        let names: Vec<&str> = config.commands.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec![
+                "true",
+                "ping a.example.com",
+                "ping b.example.com",
+                "ping web1",
+                "ping web2"
+            ]
+        );
+        let web2 = &config.commands[4];
+        assert_eq!(web2.command_line(), "ping -c 4 web2");
+        assert_eq!(web2.tags, vec!["web".to_string()]);
+        assert_eq!(web2.location.as_ref().unwrap().line, Some(14));
+
+        // Targets must be given exactly once
+        std::fs::write(
+            &main,
+            r#"
+output_dir = "/var/www/html"
+history_file= "/var/lib/ronde/history"
+name = "Ronde"
+[[matrix]]
+    run = "true"
+"#,
+        )
+        .unwrap();
+        assert!(matches!(
+            Config::load(main.to_str().unwrap()).await,
+            Err(ConfigError::MatrixError {
+                source: MatrixError::InvalidTargets,
+                ..
+            })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_load_yaml_json() {
+        let dir = tempfile::tempdir().unwrap();
+        let toml_file = dir.path().join("config.toml");
+        std::fs::write(
+            &toml_file,
+            r#"
+output_dir = "/var/www/html"
+history_file= "/var/lib/ronde/history"
+name = "Ronde"
+[templates.ping]
+    run = ["ping", "-c", "4", "{{host}}"]
+    timeout = 5
+[[commands]]
+    name = "ping localhost"
+    template = "ping"
+    vars.host = "localhost"
+    env.LANG = "C"
+"#,
+        )
+        .unwrap();
+        let yaml_file = dir.path().join("config.yml");
+        std::fs::write(
+            &yaml_file,
+            r#"
+output_dir: /var/www/html
+history_file: /var/lib/ronde/history
+name: Ronde
+default_env:
+  cwd: ~
+  shell:
+templates:
+  ping:
+    run: [ping, -c, "4", "{{host}}"]
+    timeout: 5
+commands:
+  - name: ping localhost
+    template: ping
+    vars:
+      host: localhost
+    env:
+      LANG: C
+"#,
+        )
+        .unwrap();
+        let json_file = dir.path().join("config");
+        std::fs::write(
+            &json_file,
+            r#"{
+  "output_dir": "/var/www/html",
+  "history_file": "/var/lib/ronde/history",
+  "name": "Ronde",
+  "default_env": {"cwd": null, "shell": null},
+  "templates": {
+    "ping": {"run": ["ping", "-c", "4", "{{host}}"], "timeout": 5}
+  },
+  "commands": [
+    {"name": "ping localhost", "template": "ping", "vars": {"host": "localhost"}, "env": {"LANG": "C"}}
+  ]
+}"#,
+        )
+        .unwrap();
+        let mut expected = Config::load(toml_file.to_str().unwrap()).await.unwrap();
+        expected.commands[0].location = None;
+        let mut yaml = Config::load(yaml_file.to_str().unwrap()).await.unwrap();
+        assert_eq!(
+            yaml.commands[0].location.take().unwrap().to_string(),
+            yaml_file.to_str().unwrap()
+        );
+        assert_eq!(yaml, expected);
+        // No extension, the format has to be given
+        assert!(Config::load(json_file.to_str().unwrap()).await.is_err());
+        let mut json = Config::load_with_format(json_file.to_str().unwrap(), ConfigFormat::Json)
+            .await
+            .unwrap();
+        json.commands[0].location = None;
+        assert_eq!(json, expected);
+    }
+
+    #[tokio::test]
+    async fn test_load_channels() {
+        let mut file = NamedTempFile::new().unwrap();
+        write!(
+            file,
+            r#"
+output_dir = "/var/www/html"
+history_file= "/var/lib/ronde/history"
+name = "Ronde"
+[[notifications.channels]]
+    type = "pushover"
+    token = "token123"
+    user = "user123"
+[[notifications.channels]]
+    type = "pushover"
+    token = "token456"
+    user = "user456"
+[[notifications.channels]]
+    type = "mattermost"
+    webhook_url = "https://chat.example.com/hooks/xyz"
+"#
+        )
+        .unwrap();
+        let config = Config::load(file.path().to_str().unwrap()).await.unwrap();
+        let notifications = config.notifications.unwrap();
+        assert_eq!(notifications.pushover, None);
+        assert_eq!(
+            notifications.channels,
+            vec![
+                ChannelConfig::Pushover(PushoverConfig {
+                    user: "user123".to_string(),
+                    token: "token123".to_string(),
+                    ..Default::default()
+                }),
+                ChannelConfig::Pushover(PushoverConfig {
+                    user: "user456".to_string(),
+                    token: "token456".to_string(),
+                    ..Default::default()
+                }),
+                ChannelConfig::Slack(SlackConfig {
+                    webhook_url: "https://chat.example.com/hooks/xyz".to_string(),
+                    channel: None,
+                    username: None,
+                }),
+            ]
+        );
+        assert_eq!(config.secrets.redact("token456"), "********");
+    }
+
+    #[tokio::test]
+    async fn test_load_users() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("config.toml");
+        let config = |extra: &str| {
+            format!(
+                r#"
+output_dir = "/var/www/html"
+history_file= "/var/lib/ronde/history"
+name = "Ronde"
+user = "root"
+[default_env]
+    group = "root"
+[[commands]]
+    name = "test"
+    run = "true"
+{extra}
+"#
+            )
+        };
+        std::fs::write(&file, config("    user = \"root\"")).unwrap();
+        let config_ = Config::load(file.to_str().unwrap()).await.unwrap();
+        assert_eq!((config_.uid, config_.gid), (Some(0), Some(0)));
+        assert_eq!(config_.default_env.gid, Some(0));
+        assert_eq!(config_.default_env.uid, None);
+        let command = &config_.commands[0];
+        assert_eq!((command.uid, command.gid), (Some(0), Some(0)));
+
+        std::fs::write(&file, config("    user = \"no such user\"")).unwrap();
+        let err = Config::load(file.to_str().unwrap()).await.unwrap_err();
+        assert_eq!(err.to_string(), "Command test: Unknown user no such user");
+
+        std::fs::write(&file, config("    group = \"root\"\n    gid = 0")).unwrap();
+        let err = Config::load(file.to_str().unwrap()).await.unwrap_err();
+        assert_eq!(err.to_string(), "Command test: both group and gid are set");
     }
 
     #[test]
@@ -309,17 +2374,17 @@ name = "Ronde"
             commands: vec![
                 CommandConfig {
                     name: "ping localhost".to_string(),
-                    run: "ping -c 4 localhost".to_string(),
+                    run: Some("ping -c 4 localhost".into()),
                     ..Default::default()
                 },
                 CommandConfig {
                     name: "ping localhost".to_string(),
-                    run: "ping -c 4 localhost".to_string(),
+                    run: Some("ping -c 4 localhost".into()),
                     ..Default::default()
                 },
             ],
             ..Default::default()
         };
-        assert!(config.check_unique_command_names().is_err());
+        assert_eq!(config.duplicate_command_names().len(), 1);
     }
 }
@@ -14,6 +14,12 @@ pub enum RondeError {
     /// History Error
     #[error("History Error: {0}")]
     HistoryError(#[from] crate::history::HistoryError),
+    /// Privileges cannot be dropped
+    #[error("User Error: {0}")]
+    UserError(#[from] crate::user::UserError),
+    /// Command not found in the configuration
+    #[error("Unknown command {0}")]
+    UnknownCommand(String),
     /// Cli Error
     #[error("Cli Error")]
     CliError(),
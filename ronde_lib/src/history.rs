@@ -1,11 +1,13 @@
-use crate::config::NotificationConfig;
+use crate::config::{CommandConfig, OutputRetention};
 use crate::notification::NotificationType;
-use crate::runner::{CommandError, CommandOutput, CommandResult};
+use crate::runner::{CommandError, CommandOutput, CommandResult, ResourceUsage};
+use crate::secrets::Secrets;
 use crate::summary::Summary;
 use chrono::{DateTime, Datelike, Timelike, Utc};
 use serde_derive::{Deserialize, Serialize};
 use thiserror::Error;
 use tokio::fs;
+use tracing::{debug, info};
 
 #[derive(Debug, Error)]
 /// Error type for history
@@ -74,6 +76,9 @@ pub struct CommandHistoryEntry {
     /// Command that was run
     #[serde(default)]
     pub command: String,
+    /// Resource usage of the command
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage: Option<ResourceUsage>,
 }
 impl CommandHistoryEntry {
     /// Merge in an newer entry
@@ -82,6 +87,7 @@ impl CommandHistoryEntry {
         if let Err(e) = &newer.result {
             self.result = Err((*e).clone());
             self.timestamp = newer.timestamp;
+            self.usage = newer.usage.clone();
         } else if self.result.is_err() {
             // do nothing if the newer entry is ok and the older is not
         } else {
@@ -189,6 +195,26 @@ impl CommandHistory {
             });
     }
 
+    /// Drop the outputs that are not to be kept according to the retention
+    /// policy
+    pub fn apply_output_retention(&mut self, retention: OutputRetention) {
+        match retention {
+            OutputRetention::All => {}
+            OutputRetention::FailuresAndLatest => {
+                let latest_success = self.entries.iter().rposition(|e| e.result.is_ok());
+                for (idx, entry) in self.entries.iter_mut().enumerate() {
+                    if Some(idx) == latest_success {
+                        continue;
+                    }
+                    if let Ok(ref mut output) = entry.result {
+                        output.stdout.clear();
+                        output.stderr.clear();
+                    }
+                }
+            }
+        }
+    }
+
     /// Return true if the last entry is a failure and the previous one, if any, is not
     fn is_new_failure(&self) -> bool {
         if let Some(last) = self.entries.last() {
@@ -230,16 +256,15 @@ impl CommandHistory {
         }
     }
 
-    fn is_notify_continuous_failure(&self, config: &NotificationConfig) -> bool {
-        if config.minutes_between_continuous_failure_notification == 0 {
+    fn is_notify_continuous_failure(&self, minutes_between_notifications: i64) -> bool {
+        if minutes_between_notifications == 0 {
             return false;
         }
         if let Some(last) = self.entries.last() {
             if last.result.is_err() {
                 if let Some(last_failure_notification) = self.last_failure_notification {
                     let delta = chrono::Utc::now().signed_duration_since(last_failure_notification);
-                    if delta.num_minutes() >= config.minutes_between_continuous_failure_notification
-                    {
+                    if delta.num_minutes() >= minutes_between_notifications {
                         return true;
                     }
                 }
@@ -249,18 +274,22 @@ impl CommandHistory {
     }
 
     /// Need to notify?
-    pub fn need_to_notify(&mut self, config: &NotificationConfig) -> NotificationType {
+    /// `minutes_between_notifications` is the delay between two
+    /// notifications of a continuous failure, 0 to disable them
+    pub fn need_to_notify(&mut self, minutes_between_notifications: i64) -> NotificationType {
         let ntype = if self.is_new_failure() {
             NotificationType::Failure
         } else if self.is_back_from_failure() {
             NotificationType::BackFromFailure
-        } else if self.is_notify_continuous_failure(config) {
+        } else if self.is_notify_continuous_failure(minutes_between_notifications) {
             NotificationType::ContinuousFailure
         } else {
             NotificationType::None
         };
         match ntype {
-            NotificationType::None | NotificationType::BackFromFailure => self.set_notified(true),
+            NotificationType::None | NotificationType::BackFromFailure | NotificationType::Test => {
+                self.set_notified(true)
+            }
             NotificationType::Failure | NotificationType::ContinuousFailure => {
                 self.set_notified(false)
             }
@@ -269,6 +298,32 @@ impl CommandHistory {
     }
 }
 
+/// Get the result of a command as stored in the history, with the secret
+/// values redacted from its outputs
+pub fn redacted_result(
+    result: &CommandResult,
+    secrets: &Secrets,
+) -> Result<CommandOutput, HistoryItemError> {
+    match result.result {
+        Ok(ref output) => Ok(CommandOutput {
+            exit: output.exit,
+            stdout: secrets.redact(&output.stdout),
+            stderr: secrets.redact(&output.stderr),
+        }),
+        Err(CommandError::ReturnedError(ref e)) => Err(HistoryItemError::CommandError {
+            exit: e.output.status.code().unwrap_or(-1i32),
+            stdout: secrets.redact(&String::from_utf8_lossy(&e.output.stdout)),
+            stderr: secrets.redact(&String::from_utf8_lossy(&e.output.stderr)),
+        }),
+        Err(CommandError::TimedOut(_)) => Err(HistoryItemError::Timeout {
+            timeout: result.config.timeout.0,
+        }),
+        Err(ref e) => Err(HistoryItemError::Other {
+            message: secrets.redact(&e.to_string()),
+        }),
+    }
+}
+
 /// History of commands
 #[derive(Debug, Default, Deserialize, Serialize, PartialEq)]
 pub struct History {
@@ -291,6 +346,14 @@ impl History {
             command.rotate();
         }
     }
+
+    /// Drop the outputs that are not to be kept
+    /// See `CommandHistory::apply_output_retention` for more details
+    pub fn apply_output_retention(&mut self, retention: OutputRetention) {
+        for command in self.commands.iter_mut() {
+            command.apply_output_retention(retention);
+        }
+    }
 }
 
 impl History {
@@ -299,11 +362,19 @@ impl History {
         match fs::read_to_string(history_file).await {
             Ok(contents) => {
                 let history: History = serde_json::from_str(&contents)?;
+                debug!(
+                    path = history_file,
+                    commands = history.commands.len(),
+                    "history loaded"
+                );
                 Ok(history)
             }
-            Err(ref e) if e.kind() == std::io::ErrorKind::NotFound => Ok(History {
-                commands: Vec::new(),
-            }),
+            Err(ref e) if e.kind() == std::io::ErrorKind::NotFound => {
+                info!(path = history_file, "no history file, starting afresh");
+                Ok(History {
+                    commands: Vec::new(),
+                })
+            }
             Err(e) => Err(HistoryError::IoError(e)),
         }
     }
@@ -312,6 +383,7 @@ impl History {
     pub async fn save(&self, history_file: &String) -> Result<(), HistoryError> {
         let bytes = serde_json::to_string(self)?;
         fs::write(history_file, &bytes).await?;
+        debug!(path = history_file, bytes = bytes.len(), "history saved");
         Ok(())
     }
 
@@ -321,31 +393,28 @@ impl History {
             .retain(|c| results.iter().any(|r| r.config.name == c.name));
     }
 
+    /// Purge the history of commands that are not configured anymore
+    /// Unlike `purge_from_results`, this keeps the history of the commands
+    /// that were not run this time.
+    pub fn purge_from_config(&mut self, commands: &[CommandConfig]) {
+        self.commands
+            .retain(|c| commands.iter().any(|command| command.name == c.name));
+    }
+
     /// Update the history with new results
-    pub fn update(&mut self, results: Vec<CommandResult>) {
+    /// Secret values are redacted from the command lines and the outputs.
+    pub fn update(&mut self, results: Vec<CommandResult>, secrets: &Secrets) {
         for result in results {
             let command_history = self
                 .commands
                 .iter_mut()
                 .find(|c| c.name == result.config.name);
             let entry = CommandHistoryEntry {
-                result: match result.result {
-                    Ok(output) => Ok(output),
-                    Err(CommandError::ReturnedError(e)) => Err(HistoryItemError::CommandError {
-                        exit: e.output.status.code().unwrap_or(-1i32),
-                        stdout: String::from_utf8_lossy(&e.output.stdout).to_string(),
-                        stderr: String::from_utf8_lossy(&e.output.stderr).to_string(),
-                    }),
-                    Err(CommandError::TimedOut(_)) => Err(HistoryItemError::Timeout {
-                        timeout: result.config.timeout.0,
-                    }),
-                    Err(e) => Err(HistoryItemError::Other {
-                        message: e.to_string(),
-                    }),
-                },
+                result: redacted_result(&result, secrets),
                 timestamp: chrono::Utc::now(),
                 tag: TimeTag::Minute(0),
-                command: result.config.run.clone(),
+                command: secrets.redact(&result.config.command_line()),
+                usage: result.usage,
             };
             match command_history {
                 Some(command_history) => {
@@ -402,6 +471,13 @@ mod tests {
                     timestamp: chrono::Utc::now(),
                     tag: TimeTag::Minute(0),
                     command: "testing".to_string(),
+                    usage: Some(ResourceUsage {
+                        user_time: 1200,
+                        system_time: 300,
+                        max_rss: 2048,
+                        voluntary_switches: 3,
+                        involuntary_switches: 1,
+                    }),
                 }],
                 last_failure_notification: None,
             }],
@@ -443,7 +519,7 @@ mod tests {
             CommandResult {
                 config: CommandConfig {
                     name: "test2".to_string(),
-                    run: "test2".to_string(),
+                    run: Some("test2".into()),
                     timeout: Timeout(10),
                     ..Default::default()
                 },
@@ -452,12 +528,13 @@ mod tests {
                     stdout: "".to_string(),
                     stderr: "".to_string(),
                 }),
+                usage: None,
             },
             CommandResult {
                 config: CommandConfig {
                     name: "test3".to_string(),
                     timeout: Timeout(10),
-                    run: "test3".to_string(),
+                    run: Some("test3".into()),
                     ..Default::default()
                 },
                 result: Ok(CommandOutput {
@@ -465,6 +542,7 @@ mod tests {
                     stdout: "".to_string(),
                     stderr: "".to_string(),
                 }),
+                usage: None,
             },
         ]);
         assert_eq!(
@@ -486,6 +564,39 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_update_redacts_secrets() {
+        let mut secrets = Secrets::default();
+        secrets.add("s3cr3t");
+        let mut history = History::default();
+        history.update(
+            vec![CommandResult {
+                config: CommandConfig {
+                    name: "test".to_string(),
+                    run: Some("curl -u admin:s3cr3t https://example.com".into()),
+                    ..Default::default()
+                },
+                result: Ok(CommandOutput {
+                    exit: 0,
+                    stdout: "logged in with s3cr3t".to_string(),
+                    stderr: "".to_string(),
+                }),
+                usage: None,
+            }],
+            &secrets,
+        );
+        let entry = &history.commands[0].entries[0];
+        assert_eq!(entry.command, "curl -u admin:******** https://example.com");
+        assert_eq!(
+            entry.result,
+            Ok(CommandOutput {
+                exit: 0,
+                stdout: "logged in with ********".to_string(),
+                stderr: "".to_string(),
+            })
+        );
+    }
+
     #[test]
     fn test_recreate_tags() {
         fn ch_ok(d: &str) -> CommandHistoryEntry {
@@ -498,6 +609,7 @@ mod tests {
                 timestamp: chrono::DateTime::parse_from_rfc2822(d).unwrap().to_utc(),
                 tag: TimeTag::Minute(0),
                 command: "".to_string(),
+                usage: None,
             }
         }
         let mut history = CommandHistory {
@@ -576,6 +688,7 @@ mod tests {
                 timestamp: chrono::DateTime::parse_from_rfc2822(d).unwrap().to_utc(),
                 tag: TimeTag::Minute(0),
                 command: "".to_string(),
+                usage: None,
             }
         }
         let mut history = CommandHistory {
@@ -625,6 +738,7 @@ mod tests {
                 timestamp: chrono::DateTime::parse_from_rfc2822(d).unwrap().to_utc(),
                 tag: TimeTag::Minute(0),
                 command: "".to_string(),
+                usage: None,
             }
         }
         fn ch_err(d: &str) -> CommandHistoryEntry {
@@ -637,6 +751,7 @@ mod tests {
                 timestamp: chrono::DateTime::parse_from_rfc2822(d).unwrap().to_utc(),
                 tag: TimeTag::Minute(0),
                 command: "".to_string(),
+                usage: None,
             }
         }
         struct TestCase {
@@ -774,6 +889,57 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_apply_output_retention() {
+        fn ch(is_ok: bool, out: &str) -> CommandHistoryEntry {
+            CommandHistoryEntry {
+                result: if is_ok {
+                    Ok(CommandOutput {
+                        exit: 0,
+                        stdout: out.to_string(),
+                        stderr: out.to_string(),
+                    })
+                } else {
+                    Err(HistoryItemError::CommandError {
+                        exit: 1,
+                        stdout: out.to_string(),
+                        stderr: out.to_string(),
+                    })
+                },
+                timestamp: chrono::DateTime::parse_from_rfc2822("Wed, 07 Feb 2024 19:49:43 GMT")
+                    .unwrap()
+                    .to_utc(),
+                tag: TimeTag::Minute(0),
+                command: "".to_string(),
+                usage: None,
+            }
+        }
+        let entries = vec![
+            ch(true, "ok1"),
+            ch(false, "err1"),
+            ch(true, "ok2"),
+            ch(false, "err2"),
+        ];
+        let mut history = CommandHistory {
+            name: "test".to_string(),
+            entries: entries.clone(),
+            last_failure_notification: None,
+        };
+        history.apply_output_retention(OutputRetention::All);
+        assert_eq!(history.entries, entries);
+
+        history.apply_output_retention(OutputRetention::FailuresAndLatest);
+        assert_eq!(
+            history.entries,
+            vec![
+                ch(true, ""),
+                ch(false, "err1"),
+                ch(true, "ok2"),
+                ch(false, "err2")
+            ]
+        );
+    }
+
     #[test]
     fn test_is_new_failure_back_from_failure() {
         let mut history = CommandHistory {
@@ -794,6 +960,7 @@ mod tests {
             timestamp: chrono::Utc::now(),
             tag: TimeTag::Minute(0),
             command: "".to_string(),
+            usage: None,
         });
         // single entry is ok => no new error, not back from failure
         assert!(!history.is_new_failure());
@@ -808,6 +975,7 @@ mod tests {
             timestamp: chrono::Utc::now(),
             tag: TimeTag::Minute(0),
             command: "".to_string(),
+            usage: None,
         });
         // newer entry is an error and previous one is not => new error, not back from failure
         assert!(history.is_new_failure());
@@ -822,6 +990,7 @@ mod tests {
             timestamp: chrono::Utc::now(),
             tag: TimeTag::Minute(0),
             command: "".to_string(),
+            usage: None,
         });
         // newer entry is an error and previous one is also an error => no new error, not back from failure
         assert!(!history.is_new_failure());
@@ -836,6 +1005,7 @@ mod tests {
             timestamp: chrono::Utc::now(),
             tag: TimeTag::Minute(0),
             command: "".to_string(),
+            usage: None,
         });
         // newer entry is ok and previous one is an error => no new error, back from failure
         assert!(!history.is_new_failure());
@@ -850,6 +1020,7 @@ mod tests {
             timestamp: chrono::Utc::now(),
             tag: TimeTag::Minute(0),
             command: "".to_string(),
+            usage: None,
         });
         // newer entry is ok and previous one is also ok => no new error, not back from failure
         assert!(!history.is_new_failure());
@@ -865,6 +1036,7 @@ mod tests {
             timestamp: chrono::Utc::now(),
             tag: TimeTag::Minute(0),
             command: "".to_string(),
+            usage: None,
         });
         // single entry is an error => new error, not back from failure
         assert!(history.is_new_failure());
@@ -1,4 +1,5 @@
 use crate::history::{CommandHistory, CommandHistoryEntry, History, HistoryItemError, TimeTag};
+use crate::runner::ResourceUsage;
 use crate::summary::Summary;
 use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
 use serde_derive::Serialize;
@@ -6,6 +7,7 @@ use std::collections::HashMap;
 use std::path::PathBuf;
 use thiserror::Error;
 use tokio::fs;
+use tracing::{debug, info};
 
 /// Error type for HTML generation
 #[derive(Error, Debug)]
@@ -39,6 +41,9 @@ pub struct CommandHistoryEntryDetails {
     pub message: Option<String>,
     #[serde(rename = "c")]
     pub command: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(rename = "u")]
+    pub usage: Option<ResourceUsage>,
 }
 impl CommandHistoryEntryDetails {
     /// Create a new CommandHistoryEntryDetails
@@ -79,6 +84,7 @@ impl CommandHistoryEntryDetails {
             stderr,
             message,
             command: entry.command.clone(),
+            usage: entry.usage.clone(),
         }
     }
 }
@@ -113,9 +119,11 @@ async fn write_static_file(
     let path = output_path.as_path();
     match fs::metadata(path).await {
         Ok(metadata) if metadata.len() != content.len() as u64 => {
+            debug!(path = %path.display(), "writing static file");
             fs::write(path, content).await?;
         }
         Err(_) => {
+            debug!(path = %path.display(), "writing static file");
             fs::write(path, content).await?;
         }
         _ => {}
@@ -183,8 +191,8 @@ struct MainJson {
 }
 
 /// Generate an id from a name
-/// The id is suitable as an HTML id & a filename
-fn generate_id(name: &String) -> String {
+/// The id is suitable as an HTML id, a filename and an incident key
+pub fn generate_id(name: &str) -> String {
     URL_SAFE_NO_PAD.encode(blake3::hash(name.as_bytes()).as_bytes())
 }
 
@@ -239,6 +247,7 @@ pub async fn generate_json_files(
         let mut output_path = PathBuf::from(output_dir);
         output_path.push(format!("{}.json", generate_id(&command.name)));
         let path = output_path.as_path();
+        debug!(path = %path.display(), command = %command.name, "writing command details");
         fs::write(path, json).await?;
     }
 
@@ -247,6 +256,8 @@ pub async fn generate_json_files(
     let path = output_path.as_path();
     let main = MainJson::new(summary, history, name);
     let main_json = serde_json::to_string(&main)?;
+    debug!(path = %path.display(), "writing main.json");
     fs::write(path, main_json).await?;
+    info!(output_dir, "status page updated");
     Ok(())
 }
@@ -1,3 +1,8 @@
+// The tests keep hours written with two digits and build slices with vec!
+#![cfg_attr(test, allow(clippy::zero_prefixed_literal, clippy::useless_vec))]
+
+/// Module to validate the configuration
+pub mod check;
 /// Module to load configuration
 pub mod config;
 /// Module to handle errors
@@ -6,9 +11,21 @@ pub mod error;
 pub mod history;
 /// Module to generate HTML output
 pub mod html;
+/// Module to expand matrices of commands
+pub mod matrix;
 /// Module to send notifications
 pub mod notification;
+/// Module to report the results of a run
+pub mod report;
 /// Module to run commands
 pub mod runner;
+/// Module to sandbox commands
+pub mod sandbox;
+/// Module to handle secrets
+pub mod secrets;
 /// Module to summarize results
 pub mod summary;
+/// Module to resolve command templates
+pub mod template;
+/// Module to resolve users and groups and drop privileges
+pub mod user;
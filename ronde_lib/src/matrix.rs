@@ -0,0 +1,297 @@
+use crate::template::{self, TemplateError};
+use serde_derive::Deserialize;
+use std::collections::HashMap;
+use thiserror::Error;
+use toml::{Table, Value};
+
+/// Error type for matrices
+#[derive(Debug, Error)]
+pub enum MatrixError {
+    /// Neither `targets` nor `targets_file` is set, or both are
+    #[error("Exactly one of targets and targets_file must be set")]
+    InvalidTargets,
+    /// Invalid CSV targets file
+    #[error("Invalid CSV: {0}")]
+    Csv(#[from] csv::Error),
+    /// Template Error
+    #[error("{0}")]
+    Template(#[from] TemplateError),
+    /// Expanded command without a name
+    #[error("Cannot generate a name for target {0}")]
+    MissingName(String),
+}
+
+/// Format of a targets file
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TargetsFormat {
+    /// One target per line, optionally followed by `key=value` variables,
+    /// like an Ansible INI inventory, whose `:vars` and `:children`
+    /// sections are skipped
+    Lines,
+    /// CSV with a header line, each column being a variable
+    Csv,
+}
+
+impl TargetsFormat {
+    /// Guess the format of a targets file from its extension
+    pub fn from_path(path: &str) -> Self {
+        if path.ends_with(".csv") {
+            TargetsFormat::Csv
+        } else {
+            TargetsFormat::Lines
+        }
+    }
+}
+
+/// Target of a matrix given inline
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub enum Target {
+    /// Available as the `target` variable
+    Name(String),
+    /// Variables of the target
+    Vars(Table),
+}
+
+/// Variables of a target, in order
+pub type TargetVars = Vec<(String, String)>;
+
+impl From<Target> for TargetVars {
+    fn from(target: Target) -> Self {
+        match target {
+            Target::Name(name) => vec![("target".to_string(), name)],
+            Target::Vars(vars) => vars
+                .into_iter()
+                .map(|(k, v)| match v {
+                    Value::String(s) => (k, s),
+                    v => (k, v.to_string()),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// A `[[matrix]]` section: a command expanded once per target
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Matrix {
+    /// Inline list of targets
+    pub targets: Option<Vec<Target>>,
+    /// File to read the targets from, relative to the configuration file
+    pub targets_file: Option<String>,
+    /// Format of `targets_file`, guessed from its extension by default
+    pub format: Option<TargetsFormat>,
+    /// Settings of the command, usually a `template` and `vars`
+    #[serde(flatten)]
+    pub command: Table,
+}
+
+/// Parse the content of a targets file
+pub fn parse_targets(content: &str, format: TargetsFormat) -> Result<Vec<TargetVars>, MatrixError> {
+    match format {
+        TargetsFormat::Lines => {
+            let mut targets = Vec::new();
+            // Whether the current section holds variables or child groups
+            // instead of hosts
+            let mut skip = false;
+            for line in content.lines().map(str::trim) {
+                if line.is_empty() || line.starts_with(['#', ';']) {
+                    continue;
+                }
+                if let Some(section) = line.strip_prefix('[') {
+                    let section = section.trim_end_matches(']');
+                    skip = section.ends_with(":vars") || section.ends_with(":children");
+                    continue;
+                }
+                if skip {
+                    continue;
+                }
+                let mut fields = line.split_whitespace();
+                let mut vars = vec![("target".to_string(), fields.next().unwrap().to_string())];
+                vars.extend(
+                    fields
+                        .filter_map(|f| f.split_once('='))
+                        .map(|(k, v)| (k.to_string(), v.to_string())),
+                );
+                targets.push(vars);
+            }
+            Ok(targets)
+        }
+        TargetsFormat::Csv => {
+            let mut reader = csv::ReaderBuilder::new()
+                .trim(csv::Trim::All)
+                .comment(Some(b'#'))
+                .from_reader(content.as_bytes());
+            let headers = reader.headers()?.clone();
+            let mut targets = Vec::new();
+            for record in reader.records() {
+                let record = record?;
+                targets.push(
+                    headers
+                        .iter()
+                        .zip(record.iter())
+                        .map(|(k, v)| (k.to_string(), v.to_string()))
+                        .collect(),
+                );
+            }
+            Ok(targets)
+        }
+    }
+}
+
+/// Generate the name of the command of a target
+///
+/// It is the template name followed by the `target` variable, or by the
+/// values of all the variables if there is none.
+fn generate_name(template: Option<&str>, vars: &TargetVars) -> String {
+    let target = match vars.iter().find(|(k, _)| k == "target") {
+        Some((_, v)) => v.clone(),
+        None => vars
+            .iter()
+            .map(|(_, v)| v.as_str())
+            .collect::<Vec<_>>()
+            .join(" "),
+    };
+    match template {
+        Some(template) => format!("{template} {target}"),
+        None => target,
+    }
+}
+
+/// Expand a matrix into one command per target, with their templates
+/// resolved
+///
+/// The variables of each target are added to the `vars` of the matrix.
+/// Commands whose name is neither set in the matrix nor in its template get
+/// a generated one.
+pub fn expand(
+    command: Table,
+    targets: Vec<TargetVars>,
+    templates: &HashMap<String, Table>,
+) -> Result<Vec<Table>, MatrixError> {
+    let template_name = command
+        .get("template")
+        .and_then(Value::as_str)
+        .map(str::to_string);
+    let mut commands = Vec::with_capacity(targets.len());
+    for vars in targets {
+        let mut table = command.clone();
+        let mut target_vars = Table::new();
+        for (k, v) in vars.iter() {
+            target_vars.insert(k.clone(), Value::String(v.clone()));
+        }
+        let mut overrides = Table::new();
+        overrides.insert("vars".to_string(), Value::Table(target_vars));
+        template::merge(&mut table, overrides);
+        let mut resolved = template::resolve(table, templates)?;
+        if !resolved.contains_key("name") {
+            let name = generate_name(template_name.as_deref(), &vars);
+            if name.is_empty() {
+                return Err(MatrixError::MissingName(format!("{vars:?}")));
+            }
+            resolved.insert("name".to_string(), Value::String(name));
+        }
+        commands.push(resolved);
+    }
+    Ok(commands)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_targets() {
+        let lines = r#"
+# web servers
+[web]
+web1.example.com
+web2.example.com port=8080 ansible_user=admin
+[web:vars]
+ansible_python_interpreter=/usr/bin/python3
+[all:children]
+web
+"#;
+        assert_eq!(
+            parse_targets(lines, TargetsFormat::Lines).unwrap(),
+            vec![
+                vec![("target".to_string(), "web1.example.com".to_string())],
+                vec![
+                    ("target".to_string(), "web2.example.com".to_string()),
+                    ("port".to_string(), "8080".to_string()),
+                    ("ansible_user".to_string(), "admin".to_string()),
+                ],
+            ]
+        );
+        let csv = "host, port\ndb1.example.com, 5432\n\"db2,example.com\",5433\n";
+        assert_eq!(
+            parse_targets(csv, TargetsFormat::Csv).unwrap(),
+            vec![
+                vec![
+                    ("host".to_string(), "db1.example.com".to_string()),
+                    ("port".to_string(), "5432".to_string()),
+                ],
+                vec![
+                    ("host".to_string(), "db2,example.com".to_string()),
+                    ("port".to_string(), "5433".to_string()),
+                ],
+            ]
+        );
+        assert!(parse_targets("host,port\na,1,2\n", TargetsFormat::Csv).is_err());
+    }
+
+    #[test]
+    fn test_expand() {
+        let templates: HashMap<String, Table> = toml::from_str(
+            r#"
+[ping]
+    run = "ping -c {{count}} {{target}}"
+    timeout = 5
+[tcp]
+    name = "tcp {{host}}:{{port}}"
+    run = "nc -z {{host}} {{port}}"
+"#,
+        )
+        .unwrap();
+        let matrix: Matrix = toml::from_str(
+            r#"
+template = "ping"
+targets = ["a.example.com", "b.example.com"]
+vars.count = 2
+timeout = 10
+"#,
+        )
+        .unwrap();
+        let targets = matrix
+            .targets
+            .unwrap()
+            .into_iter()
+            .map(Into::into)
+            .collect();
+        let commands = expand(matrix.command, targets, &templates).unwrap();
+        let expected: Table = toml::from_str(
+            r#"
+name = "ping a.example.com"
+run = "ping -c 2 a.example.com"
+timeout = 10
+"#,
+        )
+        .unwrap();
+        assert_eq!(commands.len(), 2);
+        assert_eq!(commands[0], expected);
+        assert_eq!(commands[1]["name"].as_str(), Some("ping b.example.com"));
+
+        // The name of the template is used when set
+        let command: Table = toml::from_str("template = \"tcp\"").unwrap();
+        let targets = parse_targets("host,port\ndb,5432\n", TargetsFormat::Csv).unwrap();
+        let commands = expand(command, targets, &templates).unwrap();
+        assert_eq!(commands[0]["name"].as_str(), Some("tcp db:5432"));
+        assert_eq!(commands[0]["run"].as_str(), Some("nc -z db 5432"));
+
+        // Targets read from a file cannot inject commands
+        let command: Table = toml::from_str("template = \"tcp\"").unwrap();
+        let targets = parse_targets("host,port\n$(reboot),22\n", TargetsFormat::Csv).unwrap();
+        let commands = expand(command, targets, &templates).unwrap();
+        assert_eq!(commands[0]["run"].as_str(), Some("nc -z '$(reboot)' 22"));
+    }
+}
@@ -1,6 +1,34 @@
-use crate::config::NotificationConfig;
-use crate::history::{CommandHistoryEntry, History};
+use crate::config::{ChannelConfig, CommandConfig, NotificationConfig, Priorities};
+use crate::history::{CommandHistoryEntry, History, HistoryItemError};
+use crate::template::TemplateError;
+use async_trait::async_trait;
+use chrono::Utc;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::time::Duration;
 use thiserror::Error;
+use tracing::{info, warn};
+
+mod alertmanager;
+/// Formatting shared by the chat channels
+mod chat;
+mod discord;
+mod exec;
+mod gotify;
+/// Incidents shared by the incident management channels
+mod incident;
+mod matrix;
+mod ntfy;
+mod opsgenie;
+mod pagerduty;
+mod pushover;
+mod slack;
+mod smtp;
+mod telegram;
+mod webhook;
+
+/// Timeout of the HTTP requests of the notifiers
+const HTTP_TIMEOUT: Duration = Duration::from_secs(30);
 
 #[derive(Debug, Error)]
 /// Error type for notifications
@@ -11,9 +39,40 @@ pub enum NotificationError {
     /// Error related to sending a notification with Pushover
     #[error("PushoverError: {0}")]
     PushoverError(String),
+    /// The server rejected the notification
+    #[error("{channel} returned {status}: {body}")]
+    Rejected {
+        channel: &'static str,
+        status: reqwest::StatusCode,
+        body: String,
+    },
+    /// Invalid payload template
+    #[error("Template Error: {0}")]
+    TemplateError(#[from] TemplateError),
+    /// The payload is not valid JSON
+    #[error("Invalid JSON payload: {0}")]
+    InvalidPayload(#[from] serde_json::Error),
+    /// Invalid HTTP method or header
+    #[error("Invalid HTTP request: {0}")]
+    InvalidRequest(String),
+    /// SMTP Error
+    #[error("SMTP Error: {0}")]
+    SmtpError(#[from] lettre::transport::smtp::Error),
+    /// Invalid email
+    #[error("Email Error: {0}")]
+    EmailError(String),
+    /// The notification program could not be run
+    #[error("Exec Error: {0}")]
+    ExecError(#[from] std::io::Error),
+    /// The notification program failed
+    #[error("Notification program {status}: {stderr}")]
+    ExecFailed {
+        status: std::process::ExitStatus,
+        stderr: String,
+    },
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 /// The type of notification to send.
 pub enum NotificationType {
     /// No notification to send.
@@ -24,102 +83,426 @@ pub enum NotificationType {
     BackFromFailure,
     /// The command has failed multiple times in a row.
     ContinuousFailure,
+    /// Sample notification sent by `ronde test-notify`
+    Test,
 }
 
-async fn send_notification(
-    config: &NotificationConfig,
-    command_name: &str,
-    notification_type: NotificationType,
-    last_run: Option<&CommandHistoryEntry>,
-) -> Result<(), NotificationError> {
-    if let Some(ref pushover) = config.pushover {
-        let client = reqwest::Client::new();
-        let mut title = match notification_type {
+impl NotificationType {
+    /// Name of the type, as given to templates
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NotificationType::None => "none",
+            NotificationType::Failure => "failure",
+            NotificationType::BackFromFailure => "back_from_failure",
+            NotificationType::ContinuousFailure => "continuous_failure",
+            NotificationType::Test => "test",
+        }
+    }
+}
+
+impl Priorities {
+    /// Get the priority of a notification, `defaults` being the priorities
+    /// of failures, reminders, recoveries and tests
+    fn get(&self, notification_type: NotificationType, defaults: [u8; 4]) -> u8 {
+        match notification_type {
+            NotificationType::Failure => self.failure.unwrap_or(defaults[0]),
+            NotificationType::ContinuousFailure => self.continuous_failure.unwrap_or(defaults[1]),
+            NotificationType::BackFromFailure => self.back_from_failure.unwrap_or(defaults[2]),
+            NotificationType::Test | NotificationType::None => defaults[3],
+        }
+    }
+}
+
+/// Event sent to every notification channel
+#[derive(Debug)]
+pub struct Notification<'a> {
+    /// Name of the command
+    pub command_name: &'a str,
+    /// Configuration of the command, if it is still configured
+    pub command: Option<&'a CommandConfig>,
+    /// Type of notification
+    pub notification_type: NotificationType,
+    /// Latest run of the command
+    pub last_run: Option<&'a CommandHistoryEntry>,
+    /// Short description of the event
+    pub title: String,
+    /// Command line and outputs of the latest run
+    pub details: String,
+    /// URL of the status page
+    pub status_page: Option<&'a str>,
+}
+
+impl<'a> Notification<'a> {
+    /// Describe the event of a command
+    pub fn new(
+        command_name: &'a str,
+        command: Option<&'a CommandConfig>,
+        notification_type: NotificationType,
+        last_run: Option<&'a CommandHistoryEntry>,
+    ) -> Self {
+        let title = match notification_type {
             NotificationType::Failure => format!("New Failure of {command_name}"),
             NotificationType::BackFromFailure => format!("Back from failure on {command_name}"),
             NotificationType::ContinuousFailure => {
                 format!("Continuous failure of {command_name}")
             }
+            NotificationType::Test => "Test notification from ronde".to_string(),
             NotificationType::None => "None".to_string(),
         };
-        let mut details = match notification_type {
+        let last_run_details = || {
+            last_run.map(|last| match last.result {
+                Ok(ref output) => format!(
+                    "{}\n>>>STDERR\n{}\n>>>STDOUT\n{}",
+                    last.command, &output.stderr, &output.stdout
+                ),
+                Err(ref e) => format!("{}\n{}", last.command, e),
+            })
+        };
+        let details = match notification_type {
             NotificationType::Failure => {
-                if let Some(last) = last_run {
-                    match last.result {
-                        Ok(ref output) => format!(
-                            "{}\n>>>STDERR\n{}\n>>>STDOUT\n{}",
-                            last.command, &output.stderr, &output.stdout
-                        ),
-                        Err(ref e) => format!("{}\n{}", last.command, e),
-                    }
-                } else {
-                    "The command has failed.".to_string()
-                }
+                last_run_details().unwrap_or_else(|| "The command has failed.".to_string())
             }
-            NotificationType::BackFromFailure => title.clone(),
-            NotificationType::ContinuousFailure => {
-                if let Some(last) = last_run {
-                    match last.result {
-                        Ok(ref output) => format!(
-                            "{}\n>>>STDERR\n{}\n>>>STDOUT\n{}",
-                            last.command, &output.stderr, &output.stdout
-                        ),
-                        Err(ref e) => format!("{}\n{}", last.command, e),
-                    }
-                } else {
-                    "The command has failed multiple times.".to_string()
-                }
+            NotificationType::ContinuousFailure => last_run_details()
+                .unwrap_or_else(|| "The command has failed multiple times.".to_string()),
+            NotificationType::Test => {
+                "If you can read this, notifications are working.".to_string()
             }
-            NotificationType::None => title.clone(),
+            NotificationType::BackFromFailure | NotificationType::None => title.clone(),
         };
-        // Truncate the message to 1024 characters.
-        if details.len() > 1024 {
-            details.drain(..1024).for_each(drop);
-        };
-        if title.len() > 255 {
-            title.drain(..255).for_each(drop);
-        };
-        let one = "1".to_string();
-        let mut form = vec![
-            ("user", &pushover.user),
-            ("token", &pushover.token),
-            ("monospace", &one),
-            ("message", &details),
-            ("title", &title),
-        ];
-        if let Some(ref url) = pushover.url {
-            form.push(("url", url));
+        Notification {
+            command_name,
+            command,
+            notification_type,
+            last_run,
+            title,
+            details,
+            status_page: None,
+        }
+    }
+
+    /// Exit code of the latest run, if it exited
+    pub fn exit(&self) -> Option<i32> {
+        match self.last_run?.result {
+            Ok(ref output) => Some(output.exit),
+            Err(HistoryItemError::CommandError { exit, .. }) => Some(exit),
+            Err(_) => None,
+        }
+    }
+
+    /// Standard output of the latest run
+    pub fn stdout(&self) -> &str {
+        match self.last_run.map(|last| &last.result) {
+            Some(Ok(output)) => &output.stdout,
+            Some(Err(HistoryItemError::CommandError { stdout, .. })) => stdout,
+            _ => "",
+        }
+    }
+
+    /// Standard error of the latest run
+    pub fn stderr(&self) -> &str {
+        match self.last_run.map(|last| &last.result) {
+            Some(Ok(output)) => &output.stderr,
+            Some(Err(HistoryItemError::CommandError { stderr, .. })) => stderr,
+            _ => "",
+        }
+    }
+
+    /// Description of the failure of the latest run
+    pub fn error(&self) -> Option<String> {
+        match self.last_run?.result {
+            Ok(_) => None,
+            Err(ref e) => Some(e.to_string()),
+        }
+    }
+
+    /// Command line of the latest run
+    pub fn command_line(&self) -> &str {
+        self.last_run.map_or("", |last| last.command.as_str())
+    }
+
+    /// Variables describing the notification, as JSON values, for the
+    /// templates of the channels
+    pub fn variables(&self) -> Vec<(&'static str, Value)> {
+        vec![
+            ("name", json!(self.command_name)),
+            ("type", json!(self.notification_type.as_str())),
+            ("title", json!(self.title)),
+            ("details", json!(self.details)),
+            ("command_line", json!(self.command_line())),
+            ("severity", json!(self.command.map(|c| c.severity))),
+            ("exit", json!(self.exit())),
+            ("stdout", json!(self.stdout())),
+            ("stderr", json!(self.stderr())),
+            ("error", json!(self.error())),
+            (
+                "timestamp",
+                json!(self.last_run.map(|last| last.timestamp.to_rfc3339())),
+            ),
+            ("now", json!(Utc::now().to_rfc3339())),
+        ]
+    }
+
+    /// Variables describing the notification, as text, unknown values being
+    /// empty
+    pub fn text_variables(&self) -> HashMap<String, String> {
+        self.variables()
+            .into_iter()
+            .map(|(k, v)| {
+                let value = match v {
+                    Value::String(s) => s,
+                    Value::Null => String::new(),
+                    v => v.to_string(),
+                };
+                (k.to_string(), value)
+            })
+            .collect()
+    }
+}
+
+/// A backend able to deliver notifications
+#[async_trait]
+pub trait Notifier: Send + Sync {
+    /// Name of the backend, for the logs
+    fn name(&self) -> &'static str;
+
+    /// Deliver a notification, using `client` for HTTP requests
+    async fn send(
+        &self,
+        client: &reqwest::Client,
+        notification: &Notification<'_>,
+    ) -> Result<(), NotificationError>;
+
+    /// Whether the backend must be told of an ongoing failure on every run,
+    /// even when no reminder is due
+    fn repeats_failures(&self) -> bool {
+        false
+    }
+}
+
+impl ChannelConfig {
+    /// Get the notifier of the channel
+    pub fn notifier(&self) -> &dyn Notifier {
+        match self {
+            ChannelConfig::Pushover(pushover) => pushover,
+            ChannelConfig::Webhook(webhook) => webhook,
+            ChannelConfig::Smtp(smtp) => smtp,
+            ChannelConfig::Ntfy(ntfy) => ntfy,
+            ChannelConfig::Gotify(gotify) => gotify,
+            ChannelConfig::Slack(slack) => slack,
+            ChannelConfig::Discord(discord) => discord,
+            ChannelConfig::Matrix(matrix) => matrix,
+            ChannelConfig::Telegram(telegram) => telegram,
+            ChannelConfig::Exec(exec) => exec,
+            ChannelConfig::PagerDuty(pagerduty) => pagerduty,
+            ChannelConfig::Opsgenie(opsgenie) => opsgenie,
+            ChannelConfig::Alertmanager(alertmanager) => alertmanager,
         }
-        let response = client
-            .post("https://api.pushover.net/1/messages.json")
-            .form(&form)
-            .send()
-            .await?;
-        if !response.status().is_success() {
-            return Err(NotificationError::PushoverError(format!(
-                "Failed to send notification to pushover: {}",
-                response.text().await?
-            )));
+    }
+}
+
+/// Turn an unsuccessful response into an error
+async fn check_response(
+    channel: &'static str,
+    response: reqwest::Response,
+) -> Result<(), NotificationError> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(());
+    }
+    Err(NotificationError::Rejected {
+        channel,
+        status,
+        body: response.text().await?,
+    })
+}
+
+/// Build the HTTP client shared by the notifiers
+///
+/// Requests time out so that an unresponsive endpoint does not block the
+/// run.
+fn http_client() -> Result<reqwest::Client, NotificationError> {
+    Ok(reqwest::Client::builder().timeout(HTTP_TIMEOUT).build()?)
+}
+
+/// Get the notifiers of every configured channel
+fn notifiers(config: &NotificationConfig) -> Vec<&dyn Notifier> {
+    let mut notifiers: Vec<&dyn Notifier> = Vec::new();
+    if let Some(ref pushover) = config.pushover {
+        notifiers.push(pushover);
+    }
+    notifiers.extend(config.channels.iter().map(ChannelConfig::notifier));
+    notifiers
+}
+
+/// Send a notification through every configured channel
+///
+/// A failing channel does not prevent the others from being notified; the
+/// first error is returned once they all have been tried.
+async fn send_notification(
+    client: &reqwest::Client,
+    notifiers: &[&dyn Notifier],
+    notification: &Notification<'_>,
+) -> Result<(), NotificationError> {
+    let mut result = Ok(());
+    for notifier in notifiers {
+        match notifier.send(client, notification).await {
+            Ok(()) => info!(
+                command = notification.command_name,
+                kind = ?notification.notification_type,
+                channel = notifier.name(),
+                "notification sent"
+            ),
+            Err(e) => {
+                warn!(
+                    command = notification.command_name,
+                    channel = notifier.name(),
+                    error = %e,
+                    "unable to send notification"
+                );
+                if result.is_ok() {
+                    result = Err(e);
+                }
+            }
         }
     }
-    Ok(())
+    result
+}
+
+/// Send a sample notification through each configured channel
+pub async fn send_test_notification(config: &NotificationConfig) -> Result<(), NotificationError> {
+    let mut notification = Notification::new("ronde", None, NotificationType::Test, None);
+    notification.status_page = config.status_page.as_deref();
+    send_notification(&http_client()?, &notifiers(config), &notification).await
 }
 
 pub async fn check_and_send_notifications(
     config: &NotificationConfig,
+    commands: &[CommandConfig],
     history: &mut History,
 ) -> Result<(), NotificationError> {
+    let client = http_client()?;
+    let notifiers = notifiers(config);
+    let repeating: Vec<&dyn Notifier> = notifiers
+        .iter()
+        .copied()
+        .filter(|n| n.repeats_failures())
+        .collect();
+    let mut result = Ok(());
     for command_history in &mut history.commands {
-        let ntype = command_history.need_to_notify(config);
-        if ntype != NotificationType::None {
-            send_notification(
-                config,
+        let command = commands.iter().find(|c| c.name == command_history.name);
+        let minutes = command.map_or(
+            config.minutes_between_continuous_failure_notification,
+            |c| c.get_minutes_between_continuous_failure_notification(config),
+        );
+        let enabled = command.is_none_or(|c| c.notifications_enabled());
+        let ntype = command_history.need_to_notify(minutes);
+        let failing = command_history
+            .entries
+            .last()
+            .is_some_and(|e| e.result.is_err());
+        let (ntype, notifiers) = match ntype {
+            NotificationType::None if failing => {
+                (NotificationType::ContinuousFailure, repeating.as_slice())
+            }
+            ntype => (ntype, notifiers.as_slice()),
+        };
+        if enabled && ntype != NotificationType::None && !notifiers.is_empty() {
+            let mut notification = Notification::new(
                 &command_history.name,
+                command,
                 ntype,
                 command_history.entries.last(),
-            )
-            .await?;
+            );
+            notification.status_page = config.status_page.as_deref();
+            if let Err(e) = send_notification(&client, notifiers, &notification).await {
+                if result.is_ok() {
+                    result = Err(e);
+                }
+            }
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::PushoverConfig;
+    use crate::history::TimeTag;
+    use std::sync::Mutex;
+
+    /// Entry of a run of `df -h` which failed with `exit`, writing `stderr`
+    pub(super) fn failed_entry(exit: i32, stderr: &str) -> CommandHistoryEntry {
+        CommandHistoryEntry {
+            result: Err(HistoryItemError::CommandError {
+                exit,
+                stdout: String::new(),
+                stderr: stderr.to_string(),
+            }),
+            timestamp: Utc::now(),
+            tag: TimeTag::Minute(0),
+            command: "df -h".to_string(),
+            usage: None,
+        }
+    }
+
+    /// Notifier recording the titles of the notifications, or failing
+    struct Recorder {
+        titles: Mutex<Vec<String>>,
+        fail: bool,
+    }
+
+    #[async_trait]
+    impl Notifier for Recorder {
+        fn name(&self) -> &'static str {
+            "recorder"
         }
+
+        async fn send(
+            &self,
+            _client: &reqwest::Client,
+            notification: &Notification<'_>,
+        ) -> Result<(), NotificationError> {
+            if self.fail {
+                return Err(NotificationError::PushoverError("failed".to_string()));
+            }
+            self.titles.lock().unwrap().push(notification.title.clone());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_notifiers() {
+        let config = NotificationConfig {
+            pushover: Some(PushoverConfig::default()),
+            channels: vec![
+                ChannelConfig::Pushover(PushoverConfig::default()),
+                ChannelConfig::Pushover(PushoverConfig::default()),
+            ],
+            ..Default::default()
+        };
+        let names: Vec<_> = notifiers(&config).iter().map(|n| n.name()).collect();
+        assert_eq!(names, vec!["pushover"; 3]);
+    }
+
+    #[tokio::test]
+    async fn test_send_to_every_channel() {
+        let failing = Recorder {
+            titles: Mutex::new(Vec::new()),
+            fail: true,
+        };
+        let working = Recorder {
+            titles: Mutex::new(Vec::new()),
+            fail: false,
+        };
+        let notification = Notification::new("disk", None, NotificationType::Failure, None);
+        assert_eq!(notification.details, "The command has failed.");
+        let client = reqwest::Client::new();
+        let result = send_notification(&client, &[&failing, &working], &notification).await;
+        assert!(result.is_err());
+        assert_eq!(
+            *working.titles.lock().unwrap(),
+            vec!["New Failure of disk".to_string()]
+        );
     }
-    Ok(())
 }
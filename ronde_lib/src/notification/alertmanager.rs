@@ -0,0 +1,224 @@
+use super::{check_response, Notification, NotificationError, NotificationType, Notifier};
+use crate::config::AlertmanagerConfig;
+use async_trait::async_trait;
+use chrono::{Duration, Utc};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+
+/// How long the alert of a test notification lasts
+const TEST_DURATION_MINUTES: i64 = 5;
+
+#[async_trait]
+impl Notifier for AlertmanagerConfig {
+    fn name(&self) -> &'static str {
+        "alertmanager"
+    }
+
+    async fn send(
+        &self,
+        client: &reqwest::Client,
+        notification: &Notification<'_>,
+    ) -> Result<(), NotificationError> {
+        let url = format!("{}/api/v2/alerts", self.url.trim_end_matches('/'));
+        let response = client
+            .post(url)
+            .json(&json!([alert(self, notification)]))
+            .send()
+            .await?;
+        check_response(self.name(), response).await
+    }
+
+    /// Alertmanager resolves an alert which is not posted again within its
+    /// `resolve_timeout`
+    fn repeats_failures(&self) -> bool {
+        true
+    }
+}
+
+/// Get the labels of the alert of a command
+///
+/// They must not change between the failure and the recovery, as
+/// Alertmanager identifies alerts by their labels.
+fn labels(
+    config: &AlertmanagerConfig,
+    notification: &Notification<'_>,
+) -> BTreeMap<String, String> {
+    let mut labels: BTreeMap<String, String> = config
+        .labels
+        .iter()
+        .map(|(k, v)| (k.clone(), v.clone()))
+        .collect();
+    labels.insert(
+        "alertname".to_string(),
+        notification.command_name.to_string(),
+    );
+    labels.insert("job".to_string(), "ronde".to_string());
+    if let Some(command) = notification.command {
+        labels.insert("severity".to_string(), command.severity.to_string());
+        if !command.tags.is_empty() {
+            let mut tags = command.tags.clone();
+            tags.sort();
+            labels.insert("tags".to_string(), tags.join(","));
+        }
+    }
+    labels
+}
+
+/// Build the alert, resolved for recoveries
+///
+/// Ongoing failures are posted without an end, so that Alertmanager keeps
+/// them firing until the next run.
+fn alert(config: &AlertmanagerConfig, notification: &Notification<'_>) -> Value {
+    let now = Utc::now();
+    let at = notification.last_run.map_or(now, |last| last.timestamp);
+    let mut alert = json!({
+        "labels": labels(config, notification),
+        "annotations": {
+            "summary": notification.title,
+            "description": notification.details,
+        },
+    });
+    match notification.notification_type {
+        NotificationType::BackFromFailure => {
+            alert["endsAt"] = json!(at.to_rfc3339());
+        }
+        NotificationType::Test => {
+            alert["startsAt"] = json!(now.to_rfc3339());
+            let end = now + Duration::minutes(TEST_DURATION_MINUTES);
+            alert["endsAt"] = json!(end.to_rfc3339());
+        }
+        NotificationType::Failure => alert["startsAt"] = json!(at.to_rfc3339()),
+        NotificationType::ContinuousFailure | NotificationType::None => {}
+    }
+    if let Some(url) = notification.status_page {
+        alert["generatorURL"] = json!(url);
+    }
+    alert
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{ChannelConfig, CommandConfig, NotificationConfig, Severity};
+    use crate::history::{CommandHistory, CommandHistoryEntry, History, TimeTag};
+    use crate::notification::check_and_send_notifications;
+    use crate::notification::tests::failed_entry;
+    use crate::runner::CommandOutput;
+    use wiremock::matchers::{body_json, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[tokio::test]
+    async fn test_send() {
+        let server = MockServer::start().await;
+        let command = CommandConfig {
+            name: "disk".to_string(),
+            severity: Severity::Warning,
+            tags: vec!["storage".to_string(), "db".to_string()],
+            ..Default::default()
+        };
+        let entry = CommandHistoryEntry {
+            result: Ok(CommandOutput {
+                exit: 0,
+                stdout: String::new(),
+                stderr: String::new(),
+            }),
+            timestamp: "2024-05-01T12:00:00Z".parse().unwrap(),
+            tag: TimeTag::Minute(0),
+            command: "df -h".to_string(),
+            usage: None,
+        };
+        let recovery = Notification::new(
+            "disk",
+            Some(&command),
+            NotificationType::BackFromFailure,
+            Some(&entry),
+        );
+        Mock::given(method("POST"))
+            .and(path("/am/api/v2/alerts"))
+            .and(body_json(json!([{
+                "labels": {
+                    "alertname": "disk",
+                    "job": "ronde",
+                    "severity": "warning",
+                    "tags": "db,storage",
+                    "team": "ops",
+                },
+                "annotations": {
+                    "summary": "Back from failure on disk",
+                    "description": recovery.details,
+                },
+                "endsAt": "2024-05-01T12:00:00+00:00",
+            }])))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let config = AlertmanagerConfig {
+            url: format!("{}/am/", server.uri()),
+            labels: [("team".to_string(), "ops".to_string())].into(),
+        };
+        config
+            .send(&reqwest::Client::new(), &recovery)
+            .await
+            .unwrap();
+
+        let failure = Notification::new(
+            "disk",
+            Some(&command),
+            NotificationType::Failure,
+            Some(&entry),
+        );
+        let alert = alert(&config, &failure);
+        assert_eq!(alert["labels"], json!(labels(&config, &recovery)));
+        assert_eq!(alert["startsAt"], "2024-05-01T12:00:00+00:00");
+        assert_eq!(alert.get("endsAt"), None);
+    }
+
+    #[tokio::test]
+    async fn test_repeat_ongoing_failure() {
+        let server = MockServer::start().await;
+        let entry = failed_entry(1, "disk full\n");
+        let reminder = Notification::new(
+            "disk",
+            None,
+            NotificationType::ContinuousFailure,
+            Some(&entry),
+        );
+        assert_eq!(
+            alert(&AlertmanagerConfig::default(), &reminder).get("endsAt"),
+            None
+        );
+        Mock::given(method("POST"))
+            .and(path("/api/v2/alerts"))
+            .and(body_json(json!([{
+                "labels": {"alertname": "disk", "job": "ronde"},
+                "annotations": {
+                    "summary": "Continuous failure of disk",
+                    "description": reminder.details,
+                },
+            }])))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let config = NotificationConfig {
+            channels: vec![ChannelConfig::Alertmanager(AlertmanagerConfig {
+                url: server.uri(),
+                labels: Default::default(),
+            })],
+            minutes_between_continuous_failure_notification: 120,
+            ..Default::default()
+        };
+        // Still failing, and a reminder is not due yet
+        let mut history = History {
+            commands: vec![CommandHistory {
+                name: "disk".to_string(),
+                entries: vec![entry.clone(), entry.clone()],
+                last_failure_notification: Some(Utc::now()),
+            }],
+        };
+        check_and_send_notifications(&config, &[], &mut history)
+            .await
+            .unwrap();
+    }
+}
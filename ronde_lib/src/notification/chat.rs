@@ -0,0 +1,66 @@
+use super::{Notification, NotificationType};
+
+/// Maximum number of characters of output to post
+const EXCERPT_CHARS: usize = 1000;
+
+/// Color of a notification, as RGB
+pub fn color(notification_type: NotificationType) -> u32 {
+    match notification_type {
+        NotificationType::Failure => 0xd50200,
+        NotificationType::ContinuousFailure => 0xff9f00,
+        NotificationType::BackFromFailure => 0x2eb886,
+        NotificationType::Test | NotificationType::None => 0x439fe0,
+    }
+}
+
+/// Get the end of the standard error of a failure, or the reason of the
+/// failure when there is none, to be put in a code block
+///
+/// Nothing is returned for recoveries.
+pub fn excerpt(notification: &Notification<'_>) -> Option<String> {
+    if !matches!(
+        notification.notification_type,
+        NotificationType::Failure | NotificationType::ContinuousFailure
+    ) {
+        return None;
+    }
+    let stderr = notification.stderr().trim_end();
+    let text = if stderr.is_empty() {
+        notification.error()?
+    } else {
+        stderr.to_string()
+    };
+    let len = text.chars().count();
+    let text = if len > EXCERPT_CHARS {
+        let tail: String = text.chars().skip(len - EXCERPT_CHARS).collect();
+        format!("…{tail}")
+    } else {
+        text
+    };
+    // Do not let the output close the code block
+    Some(text.replace("```", "'''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::HistoryItemError;
+    use crate::notification::tests::failed_entry;
+
+    #[test]
+    fn test_excerpt() {
+        let mut entry = failed_entry(1, &format!("{}end```\n", "x".repeat(2000)));
+        let notification = Notification::new("c", None, NotificationType::Failure, Some(&entry));
+        let text = excerpt(&notification).unwrap();
+        assert_eq!(text.chars().count(), EXCERPT_CHARS + 1);
+        assert!(text.starts_with("…x"));
+        assert!(text.ends_with("end'''"));
+
+        entry.result = Err(HistoryItemError::Timeout { timeout: 5 });
+        let notification = Notification::new("c", None, NotificationType::Failure, Some(&entry));
+        assert_eq!(excerpt(&notification).unwrap(), "Timeout 5s");
+        let notification =
+            Notification::new("c", None, NotificationType::BackFromFailure, Some(&entry));
+        assert_eq!(excerpt(&notification), None);
+    }
+}
@@ -0,0 +1,97 @@
+use super::chat::{color, excerpt};
+use super::{check_response, Notification, NotificationError, Notifier};
+use crate::config::DiscordConfig;
+use async_trait::async_trait;
+use serde_json::{json, Value};
+
+#[async_trait]
+impl Notifier for DiscordConfig {
+    fn name(&self) -> &'static str {
+        "discord"
+    }
+
+    async fn send(
+        &self,
+        client: &reqwest::Client,
+        notification: &Notification<'_>,
+    ) -> Result<(), NotificationError> {
+        let response = client
+            .post(&self.webhook_url)
+            .json(&payload(self, notification))
+            .send()
+            .await
+            // The URL is a secret
+            .map_err(reqwest::Error::without_url)?;
+        check_response(self.name(), response).await
+    }
+}
+
+/// Build the message, with a colored embed
+fn payload(config: &DiscordConfig, notification: &Notification<'_>) -> Value {
+    let mut fields = vec![json!({
+        "name": "Command",
+        "value": notification.command_name,
+        "inline": true,
+    })];
+    if let Some(exit) = notification.exit() {
+        fields.push(json!({"name": "Exit code", "value": exit.to_string(), "inline": true}));
+    }
+    let mut embed = json!({
+        "title": notification.title,
+        "color": color(notification.notification_type),
+        "fields": fields,
+    });
+    if let Some(excerpt) = excerpt(notification) {
+        embed["description"] = json!(format!("```\n{excerpt}\n```"));
+    }
+    if let Some(url) = notification.status_page {
+        embed["url"] = json!(url);
+    }
+    if let Some(last) = notification.last_run {
+        embed["timestamp"] = json!(last.timestamp.to_rfc3339());
+    }
+    let mut payload = json!({ "embeds": [embed] });
+    if let Some(ref username) = config.username {
+        payload["username"] = json!(username);
+    }
+    payload
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notification::NotificationType;
+    use wiremock::matchers::{body_json, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[tokio::test]
+    async fn test_send() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/api/webhooks/1/token"))
+            .and(body_json(json!({
+                "username": "ronde",
+                "embeds": [{
+                    "title": "Back from failure on disk",
+                    "color": 0x2eb886,
+                    "url": "https://status.example.com",
+                    "fields": [{"name": "Command", "value": "disk", "inline": true}],
+                }],
+            })))
+            .respond_with(ResponseTemplate::new(204))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let config = DiscordConfig {
+            webhook_url: format!("{}/api/webhooks/1/token", server.uri()),
+            username: Some("ronde".to_string()),
+        };
+        let mut notification =
+            Notification::new("disk", None, NotificationType::BackFromFailure, None);
+        notification.status_page = Some("https://status.example.com");
+        config
+            .send(&reqwest::Client::new(), &notification)
+            .await
+            .unwrap();
+    }
+}
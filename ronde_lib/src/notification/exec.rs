@@ -0,0 +1,164 @@
+use super::{Notification, NotificationError, Notifier};
+use crate::config::{ExecConfig, Run};
+use async_trait::async_trait;
+use serde_json::Value;
+use std::io;
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+/// Variables too large to be put in the environment, only given on stdin
+const STDIN_ONLY: [&str; 3] = ["details", "stdout", "stderr"];
+
+#[async_trait]
+impl Notifier for ExecConfig {
+    fn name(&self) -> &'static str {
+        "exec"
+    }
+
+    async fn send(
+        &self,
+        _client: &reqwest::Client,
+        notification: &Notification<'_>,
+    ) -> Result<(), NotificationError> {
+        let mut cmd = match self.run {
+            Run::Shell(ref command_line) => {
+                let mut cmd = Command::new("sh");
+                cmd.arg("-c").arg(command_line);
+                cmd
+            }
+            Run::Argv(ref argv) => {
+                let Some((program, args)) = argv.split_first() else {
+                    return Err(
+                        io::Error::new(io::ErrorKind::InvalidInput, "empty run array").into(),
+                    );
+                };
+                let mut cmd = Command::new(program);
+                cmd.args(args);
+                cmd
+            }
+        };
+        // The program runs with the identity ronde switched to
+        let mut child = cmd
+            .envs(environment(notification))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()?;
+        let input = payload(notification);
+        let mut stdin = child.stdin.take().expect("stdin is piped");
+        let run = async move {
+            // The program may not read its input
+            let _ = stdin.write_all(input.as_bytes()).await;
+            drop(stdin);
+            child.wait_with_output().await
+        };
+        let timeout = Duration::from_secs(self.timeout.0 as u64);
+        let output = tokio::time::timeout(timeout, run).await.map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::TimedOut,
+                format!("timed out after {}s", self.timeout.0),
+            )
+        })??;
+        if !output.status.success() {
+            return Err(NotificationError::ExecFailed {
+                status: output.status,
+                stderr: String::from_utf8_lossy(&output.stderr)
+                    .trim_end()
+                    .to_string(),
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Get the `RONDE_*` environment variables describing the notification
+fn environment(notification: &Notification<'_>) -> Vec<(String, String)> {
+    notification
+        .text_variables()
+        .into_iter()
+        .filter(|(name, _)| !STDIN_ONLY.contains(&name.as_str()))
+        .map(|(name, value)| {
+            let name = match name.as_str() {
+                "name" => "COMMAND".to_string(),
+                "type" => "STATE".to_string(),
+                name => name.to_uppercase(),
+            };
+            (format!("RONDE_{name}"), value)
+        })
+        .collect()
+}
+
+/// Get the JSON object written to the standard input of the program
+fn payload(notification: &Notification<'_>) -> String {
+    let object: serde_json::Map<String, Value> = notification
+        .variables()
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v))
+        .collect();
+    Value::Object(object).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Timeout;
+    use crate::notification::tests::failed_entry;
+    use crate::notification::NotificationType;
+
+    #[tokio::test]
+    async fn test_send() {
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("out");
+        let config = ExecConfig {
+            run: Run::Shell(format!(
+                "{{ echo \"$RONDE_COMMAND $RONDE_STATE $RONDE_EXIT $RONDE_STDERR.\"; cat; }} > {}",
+                out.display()
+            )),
+            timeout: Timeout(5),
+        };
+        let entry = failed_entry(2, "disk full\n");
+        let notification = Notification::new("disk", None, NotificationType::Failure, Some(&entry));
+        config
+            .send(&reqwest::Client::new(), &notification)
+            .await
+            .unwrap();
+
+        let out = std::fs::read_to_string(out).unwrap();
+        let (env, stdin) = out.split_once('\n').unwrap();
+        assert_eq!(env, "disk failure 2 .");
+        let stdin: Value = serde_json::from_str(stdin).unwrap();
+        assert_eq!(stdin["name"], "disk");
+        assert_eq!(stdin["stderr"], "disk full\n");
+    }
+
+    #[tokio::test]
+    async fn test_failure() {
+        let notification = Notification::new("disk", None, NotificationType::Test, None);
+        let config = ExecConfig {
+            run: Run::Argv(vec![
+                "sh".to_string(),
+                "-c".to_string(),
+                "echo no gateway >&2; exit 3".to_string(),
+            ]),
+            timeout: Timeout(5),
+        };
+        let err = config
+            .send(&reqwest::Client::new(), &notification)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().ends_with("exit status: 3: no gateway"));
+
+        let config = ExecConfig {
+            run: Run::Shell("sleep 5".to_string()),
+            timeout: Timeout(1),
+        };
+        let err = config
+            .send(&reqwest::Client::new(), &notification)
+            .await
+            .unwrap_err();
+        assert_eq!(err.to_string(), "Exec Error: timed out after 1s");
+    }
+}
@@ -0,0 +1,108 @@
+use super::{check_response, Notification, NotificationError, NotificationType, Notifier};
+use crate::config::GotifyConfig;
+use async_trait::async_trait;
+use serde_json::{json, Value};
+
+/// Default priorities of failures, reminders, recoveries and tests
+const PRIORITIES: [u8; 4] = [8, 5, 2, 5];
+
+#[async_trait]
+impl Notifier for GotifyConfig {
+    fn name(&self) -> &'static str {
+        "gotify"
+    }
+
+    async fn send(
+        &self,
+        client: &reqwest::Client,
+        notification: &Notification<'_>,
+    ) -> Result<(), NotificationError> {
+        let url = format!("{}/message", self.server.trim_end_matches('/'));
+        let response = client
+            .post(url)
+            .header("X-Gotify-Key", &self.token)
+            .json(&payload(self, notification))
+            .send()
+            .await?;
+        check_response(self.name(), response).await
+    }
+}
+
+/// Build the JSON message to send
+fn payload(config: &GotifyConfig, notification: &Notification<'_>) -> Value {
+    let has_output = matches!(
+        notification.notification_type,
+        NotificationType::Failure | NotificationType::ContinuousFailure
+    ) && notification.last_run.is_some();
+    let message = if config.markdown && has_output {
+        format!("```\n{}\n```", notification.details.replace("```", "'''"))
+    } else {
+        notification.details.clone()
+    };
+    let mut payload = json!({
+        "title": notification.title,
+        "message": message,
+        "priority": config.priorities.get(notification.notification_type, PRIORITIES),
+    });
+    if config.markdown {
+        payload["extras"]["client::display"] = json!({"contentType": "text/markdown"});
+    }
+    if let Some(ref click) = config.click {
+        payload["extras"]["client::notification"] = json!({"click": {"url": click}});
+    }
+    payload
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::HistoryItemError;
+    use crate::notification::tests::failed_entry;
+    use wiremock::matchers::{body_json, header, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[tokio::test]
+    async fn test_send() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/gotify/message"))
+            .and(header("X-Gotify-Key", "AbCdEf"))
+            .and(body_json(json!({
+                "title": "New Failure of disk",
+                "message": "```\ndf -h\nTimeout 5s\n```",
+                "priority": 8,
+                "extras": {
+                    "client::display": {"contentType": "text/markdown"},
+                    "client::notification": {"click": {"url": "https://status.example.com"}},
+                },
+            })))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let config = GotifyConfig {
+            server: format!("{}/gotify/", server.uri()),
+            token: "AbCdEf".to_string(),
+            markdown: true,
+            click: Some("https://status.example.com".to_string()),
+            ..Default::default()
+        };
+        let mut entry = failed_entry(1, "");
+        entry.result = Err(HistoryItemError::Timeout { timeout: 5 });
+        let notification = Notification::new("disk", None, NotificationType::Failure, Some(&entry));
+        config
+            .send(&reqwest::Client::new(), &notification)
+            .await
+            .unwrap();
+
+        let notification = Notification::new(
+            "disk",
+            None,
+            NotificationType::BackFromFailure,
+            Some(&entry),
+        );
+        let payload = payload(&config, &notification);
+        assert_eq!(payload["priority"], 2);
+        assert_eq!(payload["message"], "Back from failure on disk");
+    }
+}
@@ -0,0 +1,64 @@
+use super::{Notification, NotificationType};
+use crate::config::Severity;
+use crate::html::generate_id;
+
+/// What to do with the incident of a command
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Action {
+    /// Open the incident, or update it if it is already open
+    Trigger,
+    /// Close the incident
+    Resolve,
+}
+
+/// Get the actions to take for a notification
+///
+/// Reminders of ongoing failures do nothing, the incident is already open.
+/// Test notifications open an incident and close it right away.
+pub fn actions(notification_type: NotificationType) -> &'static [Action] {
+    match notification_type {
+        NotificationType::Failure => &[Action::Trigger],
+        NotificationType::BackFromFailure => &[Action::Resolve],
+        NotificationType::Test => &[Action::Trigger, Action::Resolve],
+        NotificationType::ContinuousFailure | NotificationType::None => &[],
+    }
+}
+
+/// Key of the incidents of test notifications, which no command can have
+const TEST_DEDUP_KEY: &str = "ronde-test-notification";
+
+/// Key identifying the incident of a command, stable across runs
+///
+/// Test notifications use their own key, so that they never close the
+/// incident of a command, even one named like them.
+pub fn dedup_key(notification: &Notification<'_>) -> String {
+    if notification.notification_type == NotificationType::Test {
+        return TEST_DEDUP_KEY.to_string();
+    }
+    format!("ronde-{}", generate_id(notification.command_name))
+}
+
+/// Severity of the command, `None` for test notifications and commands no
+/// longer configured
+pub fn severity(notification: &Notification<'_>) -> Option<Severity> {
+    notification.command.map(|c| c.severity)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dedup_key() {
+        let failure = Notification::new("disk", None, NotificationType::Failure, None);
+        let recovery = Notification::new("disk", None, NotificationType::BackFromFailure, None);
+        let other = Notification::new("load", None, NotificationType::Failure, None);
+        assert_eq!(dedup_key(&failure), dedup_key(&recovery));
+        assert_ne!(dedup_key(&failure), dedup_key(&other));
+        assert!(dedup_key(&failure).starts_with("ronde-"));
+        let test = Notification::new("ronde", None, NotificationType::Test, None);
+        let command = Notification::new("ronde", None, NotificationType::Failure, None);
+        assert_eq!(dedup_key(&test), "ronde-test-notification");
+        assert_ne!(dedup_key(&test), dedup_key(&command));
+    }
+}
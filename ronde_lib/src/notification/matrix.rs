@@ -0,0 +1,129 @@
+use super::chat::{color, excerpt};
+use super::{check_response, Notification, NotificationError, Notifier};
+use crate::config::MatrixConfig;
+use crate::report::xml_escape;
+use async_trait::async_trait;
+use chrono::Utc;
+use reqwest::Url;
+use serde_json::{json, Value};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Counter making the transaction IDs unique within a run
+static TRANSACTIONS: AtomicU64 = AtomicU64::new(0);
+
+#[async_trait]
+impl Notifier for MatrixConfig {
+    fn name(&self) -> &'static str {
+        "matrix"
+    }
+
+    async fn send(
+        &self,
+        client: &reqwest::Client,
+        notification: &Notification<'_>,
+    ) -> Result<(), NotificationError> {
+        let response = client
+            .put(send_url(self)?)
+            .bearer_auth(&self.access_token)
+            .json(&payload(notification))
+            .send()
+            .await?;
+        check_response(self.name(), response).await
+    }
+}
+
+/// Get the URL to send a message to the room, with a new transaction ID
+fn send_url(config: &MatrixConfig) -> Result<Url, NotificationError> {
+    let invalid =
+        |e: String| NotificationError::InvalidRequest(format!("{}: {e}", config.homeserver));
+    let mut url = Url::parse(&config.homeserver).map_err(|e| invalid(e.to_string()))?;
+    let transaction = format!(
+        "ronde-{}-{}",
+        Utc::now().timestamp_micros(),
+        TRANSACTIONS.fetch_add(1, Ordering::Relaxed)
+    );
+    url.path_segments_mut()
+        .map_err(|_| invalid("not a base URL".to_string()))?
+        .pop_if_empty()
+        .extend([
+            "_matrix",
+            "client",
+            "v3",
+            "rooms",
+            &config.room_id,
+            "send",
+            "m.room.message",
+            &transaction,
+        ]);
+    Ok(url)
+}
+
+/// Build the message, with a colored title in its HTML version
+fn payload(notification: &Notification<'_>) -> Value {
+    let mut body = notification.title.clone();
+    let mut html = format!(
+        "<p><font color=\"#{0:06x}\" data-mx-color=\"#{0:06x}\">●</font> <strong>{1}</strong></p>",
+        color(notification.notification_type),
+        xml_escape(&notification.title)
+    );
+    if let Some(excerpt) = excerpt(notification) {
+        body.push_str(&format!("\n```\n{excerpt}\n```"));
+        html.push_str(&format!("<pre><code>{}</code></pre>", xml_escape(&excerpt)));
+    }
+    if let Some(url) = notification.status_page {
+        body.push_str(&format!("\n{url}"));
+        html.push_str(&format!(
+            "<p><a href=\"{}\">Status page</a></p>",
+            xml_escape(url)
+        ));
+    }
+    json!({
+        "msgtype": "m.text",
+        "body": body,
+        "format": "org.matrix.custom.html",
+        "formatted_body": html,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notification::tests::failed_entry;
+    use crate::notification::NotificationType;
+    use wiremock::matchers::{body_json, header, method, path_regex};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[tokio::test]
+    async fn test_send() {
+        let server = MockServer::start().await;
+        Mock::given(method("PUT"))
+            .and(path_regex(
+                r"^/_matrix/client/v3/rooms/!room:example\.com/send/m\.room\.message/ronde-\d+-\d+$",
+            ))
+            .and(header("Authorization", "Bearer syt_s3cr3t"))
+            .and(body_json(json!({
+                "msgtype": "m.text",
+                "body": "New Failure of disk\n```\n<disk> full\n```",
+                "format": "org.matrix.custom.html",
+                "formatted_body": "<p><font color=\"#d50200\" data-mx-color=\"#d50200\">●</font> <strong>New Failure of disk</strong></p><pre><code>&lt;disk&gt; full</code></pre>",
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({"event_id": "$1"})))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let config = MatrixConfig {
+            homeserver: format!("{}/", server.uri()),
+            room_id: "!room:example.com".to_string(),
+            access_token: "syt_s3cr3t".to_string(),
+            access_token_file: None,
+        };
+        let entry = failed_entry(1, "<disk> full\n");
+        let notification = Notification::new("disk", None, NotificationType::Failure, Some(&entry));
+        config
+            .send(&reqwest::Client::new(), &notification)
+            .await
+            .unwrap();
+
+        assert_ne!(send_url(&config).unwrap(), send_url(&config).unwrap());
+    }
+}
@@ -0,0 +1,105 @@
+use super::{check_response, Notification, NotificationError, NotificationType, Notifier};
+use crate::config::NtfyConfig;
+use async_trait::async_trait;
+use serde_json::{json, Value};
+
+/// Server used when none is configured
+const DEFAULT_SERVER: &str = "https://ntfy.sh";
+
+/// Default priorities of failures, reminders, recoveries and tests
+const PRIORITIES: [u8; 4] = [4, 3, 2, 3];
+
+#[async_trait]
+impl Notifier for NtfyConfig {
+    fn name(&self) -> &'static str {
+        "ntfy"
+    }
+
+    async fn send(
+        &self,
+        client: &reqwest::Client,
+        notification: &Notification<'_>,
+    ) -> Result<(), NotificationError> {
+        let server = self.server.as_deref().unwrap_or(DEFAULT_SERVER);
+        let mut request = client
+            .post(server.trim_end_matches('/'))
+            .json(&payload(self, notification));
+        if let Some(ref token) = self.token {
+            request = request.bearer_auth(token);
+        }
+        check_response(self.name(), request.send().await?).await
+    }
+}
+
+/// Build the JSON message to publish
+fn payload(config: &NtfyConfig, notification: &Notification<'_>) -> Value {
+    // Shown as emojis by the ntfy clients
+    let emoji = match notification.notification_type {
+        NotificationType::Failure | NotificationType::ContinuousFailure => "rotating_light",
+        NotificationType::BackFromFailure => "white_check_mark",
+        NotificationType::Test | NotificationType::None => "bell",
+    };
+    let mut tags = vec![emoji.to_string()];
+    tags.extend(config.tags.iter().cloned());
+    let mut payload = json!({
+        "topic": config.topic,
+        "title": notification.title,
+        "message": notification.details,
+        "priority": config.priorities.get(notification.notification_type, PRIORITIES),
+        "tags": tags,
+    });
+    if let Some(ref click) = config.click {
+        payload["click"] = json!(click);
+    }
+    payload
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Priorities;
+    use wiremock::matchers::{body_json, header, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[tokio::test]
+    async fn test_send() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/"))
+            .and(header("Authorization", "Bearer tk_s3cr3t"))
+            .and(body_json(json!({
+                "topic": "alerts",
+                "title": "New Failure of disk",
+                "message": "The command has failed.",
+                "priority": 5,
+                "tags": ["rotating_light", "ronde"],
+                "click": "https://status.example.com",
+            })))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let config = NtfyConfig {
+            server: Some(format!("{}/", server.uri())),
+            topic: "alerts".to_string(),
+            token: Some("tk_s3cr3t".to_string()),
+            priorities: Priorities {
+                failure: Some(5),
+                ..Default::default()
+            },
+            tags: vec!["ronde".to_string()],
+            click: Some("https://status.example.com".to_string()),
+            ..Default::default()
+        };
+        let notification = Notification::new("disk", None, NotificationType::Failure, None);
+        config
+            .send(&reqwest::Client::new(), &notification)
+            .await
+            .unwrap();
+
+        let notification = Notification::new("disk", None, NotificationType::BackFromFailure, None);
+        let payload = payload(&config, &notification);
+        assert_eq!(payload["priority"], 2);
+        assert_eq!(payload["tags"][0], "white_check_mark");
+    }
+}
@@ -0,0 +1,144 @@
+use super::incident::{actions, dedup_key, severity, Action};
+use super::{check_response, Notification, NotificationError, Notifier};
+use crate::config::{OpsgenieConfig, Severity};
+use async_trait::async_trait;
+use reqwest::Url;
+use serde_json::{json, Value};
+
+/// Default URL of the API
+const DEFAULT_API_URL: &str = "https://api.opsgenie.com";
+
+/// Maximum number of characters of the message of an alert
+const MESSAGE_CHARS: usize = 130;
+
+/// Maximum number of characters of the description of an alert
+const DESCRIPTION_CHARS: usize = 15000;
+
+#[async_trait]
+impl Notifier for OpsgenieConfig {
+    fn name(&self) -> &'static str {
+        "opsgenie"
+    }
+
+    async fn send(
+        &self,
+        client: &reqwest::Client,
+        notification: &Notification<'_>,
+    ) -> Result<(), NotificationError> {
+        for action in actions(notification.notification_type) {
+            let (url, body) = match action {
+                Action::Trigger => (self.url(&[])?, alert(self, notification)),
+                Action::Resolve => {
+                    let alias = dedup_key(notification);
+                    let mut url = self.url(&[&alias, "close"])?;
+                    url.query_pairs_mut().append_pair("identifierType", "alias");
+                    (url, json!({"source": "ronde", "note": notification.title}))
+                }
+            };
+            let response = client
+                .post(url)
+                .header("Authorization", format!("GenieKey {}", self.api_key))
+                .json(&body)
+                .send()
+                .await?;
+            check_response(self.name(), response).await?;
+        }
+        Ok(())
+    }
+}
+
+impl OpsgenieConfig {
+    /// Get the URL of the alerts, or of one of their actions
+    fn url(&self, segments: &[&str]) -> Result<Url, NotificationError> {
+        let api_url = self.api_url.as_deref().unwrap_or(DEFAULT_API_URL);
+        let invalid = |e: String| NotificationError::InvalidRequest(format!("{api_url}: {e}"));
+        let mut url = Url::parse(api_url).map_err(|e| invalid(e.to_string()))?;
+        url.path_segments_mut()
+            .map_err(|_| invalid("not a base URL".to_string()))?
+            .pop_if_empty()
+            .extend(["v2", "alerts"])
+            .extend(segments);
+        Ok(url)
+    }
+}
+
+/// Build the alert to create
+fn alert(config: &OpsgenieConfig, notification: &Notification<'_>) -> Value {
+    let priority = match severity(notification) {
+        Some(Severity::Critical) => "P1",
+        Some(Severity::Warning) => "P3",
+        None => "P5",
+    };
+    let mut details = json!({"command": notification.command_name});
+    if let Some(exit) = notification.exit() {
+        details["exit"] = json!(exit.to_string());
+    }
+    if let Some(url) = notification.status_page {
+        details["status_page"] = json!(url);
+    }
+    json!({
+        "message": notification.title.chars().take(MESSAGE_CHARS).collect::<String>(),
+        "alias": dedup_key(notification),
+        "description": notification.details.chars().take(DESCRIPTION_CHARS).collect::<String>(),
+        "priority": priority,
+        "source": "ronde",
+        "tags": config.tags,
+        "details": details,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notification::NotificationType;
+    use wiremock::matchers::{body_partial_json, header, method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[tokio::test]
+    async fn test_create_and_close() {
+        let server = MockServer::start().await;
+        let failure = Notification::new("disk", None, NotificationType::Failure, None);
+        let alias = dedup_key(&failure);
+        Mock::given(method("POST"))
+            .and(path("/v2/alerts"))
+            .and(header("Authorization", "GenieKey k3y"))
+            .and(body_partial_json(json!({
+                "message": "New Failure of disk",
+                "alias": alias,
+                "priority": "P5",
+                "tags": ["ronde"],
+            })))
+            .respond_with(ResponseTemplate::new(202))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path(format!("/v2/alerts/{alias}/close")))
+            .and(query_param("identifierType", "alias"))
+            .and(header("Authorization", "GenieKey k3y"))
+            .respond_with(ResponseTemplate::new(202))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let config = OpsgenieConfig {
+            api_key: "k3y".to_string(),
+            api_key_file: None,
+            api_url: Some(server.uri()),
+            tags: vec!["ronde".to_string()],
+        };
+        config
+            .send(&reqwest::Client::new(), &failure)
+            .await
+            .unwrap();
+        let reminder = Notification::new("disk", None, NotificationType::ContinuousFailure, None);
+        config
+            .send(&reqwest::Client::new(), &reminder)
+            .await
+            .unwrap();
+        let recovery = Notification::new("disk", None, NotificationType::BackFromFailure, None);
+        config
+            .send(&reqwest::Client::new(), &recovery)
+            .await
+            .unwrap();
+    }
+}
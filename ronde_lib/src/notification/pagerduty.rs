@@ -0,0 +1,150 @@
+use super::incident::{actions, dedup_key, severity, Action};
+use super::{check_response, Notification, NotificationError, Notifier};
+use crate::config::{PagerDutyConfig, Severity};
+use async_trait::async_trait;
+use serde_json::{json, Value};
+
+/// Default URL of the Events API v2
+const DEFAULT_URL: &str = "https://events.pagerduty.com/v2/enqueue";
+
+/// Maximum number of characters of the summary of an event
+const SUMMARY_CHARS: usize = 1024;
+
+#[async_trait]
+impl Notifier for PagerDutyConfig {
+    fn name(&self) -> &'static str {
+        "pagerduty"
+    }
+
+    async fn send(
+        &self,
+        client: &reqwest::Client,
+        notification: &Notification<'_>,
+    ) -> Result<(), NotificationError> {
+        for action in actions(notification.notification_type) {
+            let response = client
+                .post(self.url.as_deref().unwrap_or(DEFAULT_URL))
+                .json(&event(self, notification, *action))
+                .send()
+                .await?;
+            check_response(self.name(), response).await?;
+        }
+        Ok(())
+    }
+}
+
+/// Build the event to enqueue
+fn event(config: &PagerDutyConfig, notification: &Notification<'_>, action: Action) -> Value {
+    let mut event = json!({
+        "routing_key": config.routing_key,
+        "dedup_key": dedup_key(notification),
+    });
+    match action {
+        Action::Resolve => {
+            event["event_action"] = json!("resolve");
+            return event;
+        }
+        Action::Trigger => event["event_action"] = json!("trigger"),
+    }
+    let severity = match severity(notification) {
+        Some(Severity::Critical) => "critical",
+        Some(Severity::Warning) => "warning",
+        None => "info",
+    };
+    event["payload"] = json!({
+        "summary": notification.title.chars().take(SUMMARY_CHARS).collect::<String>(),
+        "source": notification.command_name,
+        "severity": severity,
+        "custom_details": {
+            "command_line": notification.command_line(),
+            "exit": notification.exit(),
+            "details": notification.details,
+        },
+    });
+    if let Some(last) = notification.last_run {
+        event["payload"]["timestamp"] = json!(last.timestamp.to_rfc3339());
+    }
+    event["client"] = json!("ronde");
+    if let Some(url) = notification.status_page {
+        event["client_url"] = json!(url);
+        event["links"] = json!([{"href": url, "text": "Status page"}]);
+    }
+    event
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::CommandConfig;
+    use crate::notification::NotificationType;
+    use wiremock::matchers::{body_partial_json, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[tokio::test]
+    async fn test_trigger_and_resolve() {
+        let server = MockServer::start().await;
+        let command = CommandConfig {
+            name: "disk".to_string(),
+            severity: Severity::Warning,
+            ..Default::default()
+        };
+        let failure = Notification::new("disk", Some(&command), NotificationType::Failure, None);
+        let key = dedup_key(&failure);
+        Mock::given(method("POST"))
+            .and(path("/v2/enqueue"))
+            .and(body_partial_json(json!({
+                "routing_key": "R0UT1NG",
+                "event_action": "trigger",
+                "dedup_key": key,
+                "payload": {
+                    "summary": "New Failure of disk",
+                    "source": "disk",
+                    "severity": "warning",
+                },
+            })))
+            .respond_with(ResponseTemplate::new(202))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/v2/enqueue"))
+            .and(body_partial_json(json!({
+                "event_action": "resolve",
+                "dedup_key": key,
+            })))
+            .respond_with(ResponseTemplate::new(202))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let config = PagerDutyConfig {
+            routing_key: "R0UT1NG".to_string(),
+            routing_key_file: None,
+            url: Some(format!("{}/v2/enqueue", server.uri())),
+        };
+        config
+            .send(&reqwest::Client::new(), &failure)
+            .await
+            .unwrap();
+        // No duplicate incident for reminders
+        let reminder = Notification::new(
+            "disk",
+            Some(&command),
+            NotificationType::ContinuousFailure,
+            None,
+        );
+        config
+            .send(&reqwest::Client::new(), &reminder)
+            .await
+            .unwrap();
+        let recovery = Notification::new(
+            "disk",
+            Some(&command),
+            NotificationType::BackFromFailure,
+            None,
+        );
+        config
+            .send(&reqwest::Client::new(), &recovery)
+            .await
+            .unwrap();
+    }
+}
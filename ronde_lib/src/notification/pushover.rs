@@ -0,0 +1,331 @@
+use super::{check_response, Notification, NotificationError, NotificationType, Notifier};
+use crate::config::{PushoverConfig, PushoverOptions};
+use crate::report::xml_escape;
+use async_trait::async_trait;
+use reqwest::multipart::Form;
+
+/// URL of the messages API
+const API_URL: &str = "https://api.pushover.net/1/messages.json";
+
+/// Maximum number of characters of a message
+const MESSAGE_CHARS: usize = 1024;
+
+/// Maximum number of characters of a title
+const TITLE_CHARS: usize = 250;
+
+/// Seconds between two repetitions of an emergency notification
+const EMERGENCY_RETRY: u32 = 60;
+
+/// Seconds during which an emergency notification is repeated
+const EMERGENCY_EXPIRE: u32 = 3600;
+
+#[async_trait]
+impl Notifier for PushoverConfig {
+    fn name(&self) -> &'static str {
+        "pushover"
+    }
+
+    async fn send(
+        &self,
+        client: &reqwest::Client,
+        notification: &Notification<'_>,
+    ) -> Result<(), NotificationError> {
+        send_pushover(self, client, notification, API_URL).await
+    }
+}
+
+/// Get the options of a notification: the ones of the command come first,
+/// then the ones of the type of notification, then the common ones
+fn options(pushover: &PushoverConfig, notification: &Notification<'_>) -> PushoverOptions {
+    let by_type = match notification.notification_type {
+        NotificationType::Failure => &pushover.failure,
+        NotificationType::ContinuousFailure => &pushover.continuous_failure,
+        NotificationType::BackFromFailure => &pushover.back_from_failure,
+        NotificationType::Test | NotificationType::None => &PushoverOptions::default(),
+    };
+    let options = by_type.or(&pushover.options);
+    match notification
+        .command
+        .and_then(|c| c.notifications.as_ref())
+        .and_then(|n| n.pushover.as_ref())
+    {
+        Some(command) => command.or(&options),
+        None => options,
+    }
+}
+
+/// Keep the beginning of a text, marking the cut with an ellipsis
+fn truncate(text: &str, max_chars: usize) -> String {
+    if text.chars().count() <= max_chars {
+        return text.to_string();
+    }
+    if max_chars == 0 {
+        return String::new();
+    }
+    let mut truncated: String = text.chars().take(max_chars - 1).collect();
+    truncated.push('…');
+    truncated
+}
+
+/// Format the details as HTML, with the command line in bold
+fn to_html(details: &str) -> String {
+    match details.split_once('\n') {
+        Some((first, rest)) => format!("<b>{}</b>\n{}", xml_escape(first), xml_escape(rest)),
+        None => xml_escape(details),
+    }
+}
+
+/// Escape `text` into `out` as long as it stays within `limit` characters,
+/// never cutting an entity
+///
+/// Returns whether all of `text` fitted.
+fn push_escaped(out: &mut String, len: &mut usize, text: &str, limit: usize) -> bool {
+    let mut buf = [0; 4];
+    for c in text.chars() {
+        let escaped = xml_escape(c.encode_utf8(&mut buf));
+        let n = escaped.chars().count();
+        if *len + n > limit {
+            return false;
+        }
+        out.push_str(&escaped);
+        *len += n;
+    }
+    true
+}
+
+/// Get the message to send, fitting in the limit of Pushover
+fn message(details: &str, html: bool) -> String {
+    if !html {
+        return truncate(details, MESSAGE_CHARS);
+    }
+    let message = to_html(details);
+    if message.chars().count() <= MESSAGE_CHARS {
+        return message;
+    }
+    // Escaping makes the text longer: cut the escaped text, keeping room for
+    // the ellipsis
+    let limit = MESSAGE_CHARS - 1;
+    let mut out = String::new();
+    let mut len = 0;
+    let rest = match details.split_once('\n') {
+        Some((first, rest)) => {
+            out.push_str("<b>");
+            len += 3;
+            // Keep room for "</b>\n"
+            let fitted = push_escaped(&mut out, &mut len, first, limit - 5);
+            out.push_str("</b>\n");
+            len += 5;
+            if !fitted {
+                out.push('…');
+                return out;
+            }
+            rest
+        }
+        None => details,
+    };
+    push_escaped(&mut out, &mut len, rest, limit);
+    out.push('…');
+    out
+}
+
+/// Build the form of a notification
+///
+/// Pushover only accepts images as attachments: details which do not fit in
+/// a message are truncated, and the notification links to `url`, or else to
+/// the status page, where the full output can be read.
+fn form(pushover: &PushoverConfig, notification: &Notification<'_>) -> Form {
+    let options = options(pushover, notification);
+    let html = options.html.unwrap_or(false);
+    let mut form = Form::new()
+        .text("user", pushover.user.clone())
+        .text("token", pushover.token.clone())
+        .text("title", truncate(&notification.title, TITLE_CHARS))
+        .text("message", message(&notification.details, html));
+    form = if html {
+        form.text("html", "1")
+    } else {
+        form.text("monospace", "1")
+    };
+    if let Some(priority) = options.priority {
+        form = form.text("priority", priority.to_string());
+        if priority >= 2 {
+            form = form
+                .text("retry", EMERGENCY_RETRY.to_string())
+                .text("expire", EMERGENCY_EXPIRE.to_string());
+        }
+    }
+    if let Some(sound) = options.sound {
+        form = form.text("sound", sound);
+    }
+    if let Some(device) = options.device {
+        form = form.text("device", device);
+    }
+    if let Some(ttl) = options.ttl {
+        form = form.text("ttl", ttl.to_string());
+    }
+    if let Some(url) = pushover.url.as_deref().or(notification.status_page) {
+        form = form.text("url", url.to_string());
+    }
+    form
+}
+
+/// Send a notification through Pushover
+async fn send_pushover(
+    pushover: &PushoverConfig,
+    client: &reqwest::Client,
+    notification: &Notification<'_>,
+    url: &str,
+) -> Result<(), NotificationError> {
+    let response = client
+        .post(url)
+        .multipart(form(pushover, notification))
+        .send()
+        .await?;
+    check_response(pushover.name(), response).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{CommandConfig, CommandNotificationConfig};
+    use crate::notification::tests::failed_entry;
+    use wiremock::matchers::{body_string_contains, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[test]
+    fn test_options() {
+        let pushover = PushoverConfig {
+            options: PushoverOptions {
+                priority: Some(0),
+                sound: Some("pushover".to_string()),
+                ttl: Some(3600),
+                ..Default::default()
+            },
+            failure: PushoverOptions {
+                priority: Some(1),
+                sound: Some("siren".to_string()),
+                ..Default::default()
+            },
+            continuous_failure: PushoverOptions {
+                priority: Some(-1),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let command = CommandConfig {
+            name: "disk".to_string(),
+            notifications: Some(CommandNotificationConfig {
+                pushover: Some(PushoverOptions {
+                    priority: Some(2),
+                    device: Some("phone".to_string()),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let failure = Notification::new("disk", Some(&command), NotificationType::Failure, None);
+        assert_eq!(
+            options(&pushover, &failure),
+            PushoverOptions {
+                priority: Some(2),
+                sound: Some("siren".to_string()),
+                device: Some("phone".to_string()),
+                ttl: Some(3600),
+                html: None,
+            }
+        );
+        let reminder = Notification::new("load", None, NotificationType::ContinuousFailure, None);
+        let reminder = options(&pushover, &reminder);
+        assert_eq!(reminder.priority, Some(-1));
+        assert_eq!(reminder.sound, Some("pushover".to_string()));
+        let test = Notification::new("ronde", None, NotificationType::Test, None);
+        assert_eq!(options(&pushover, &test), pushover.options);
+    }
+
+    #[test]
+    fn test_message() {
+        let details = format!("ls <dir>\n{}end", "x".repeat(2000));
+        let message = message(&details, false);
+        assert_eq!(message.chars().count(), MESSAGE_CHARS);
+        assert!(message.starts_with("ls <dir>\nxxx"));
+        assert!(message.ends_with("x…"));
+
+        let html = super::message(&details, true);
+        assert_eq!(html.chars().count(), MESSAGE_CHARS);
+        assert!(html.starts_with("<b>ls &lt;dir&gt;</b>\nxxx"));
+        assert_eq!(super::message("é", true), "é");
+
+        // Entities are not cut and the message fits
+        let quotes = format!("curl\n{}", "\"".repeat(1023));
+        let html = super::message(&quotes, true);
+        assert!(html.chars().count() <= MESSAGE_CHARS);
+        assert!(html.starts_with("<b>curl</b>\n&quot;"));
+        assert!(html.ends_with("&quot;…"));
+        let html = super::message(&"'".repeat(1023), true);
+        assert!(html.chars().count() <= MESSAGE_CHARS);
+        assert!(html.ends_with("&apos;…"));
+        let long_line = format!("{}\nrest", "<".repeat(1023));
+        let html = super::message(&long_line, true);
+        assert!(html.chars().count() <= MESSAGE_CHARS);
+        assert!(html.ends_with("&lt;</b>\n…"));
+        assert_eq!(truncate("abc", 0), "");
+        assert_eq!(truncate(&"é".repeat(300), TITLE_CHARS).chars().count(), 250);
+    }
+
+    #[tokio::test]
+    async fn test_send() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/1/messages.json"))
+            .and(body_string_contains("name=\"monospace\"\r\n\r\n1\r\n"))
+            .and(body_string_contains("name=\"priority\"\r\n\r\n1\r\n"))
+            .and(body_string_contains(
+                "name=\"url\"\r\n\r\nhttps://status.example.com\r\n",
+            ))
+            .respond_with(ResponseTemplate::new(200).set_body_string(r#"{"status":1}"#))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let pushover = PushoverConfig {
+            user: "user".to_string(),
+            token: "token".to_string(),
+            failure: PushoverOptions {
+                priority: Some(1),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let entry = failed_entry(1, &format!("{}the end", "x".repeat(2000)));
+        let mut notification =
+            Notification::new("disk", None, NotificationType::Failure, Some(&entry));
+        notification.status_page = Some("https://status.example.com");
+        let url = format!("{}/1/messages.json", server.uri());
+        send_pushover(&pushover, &reqwest::Client::new(), &notification, &url)
+            .await
+            .unwrap();
+
+        // Pushover rejects anything but images as attachments
+        let body =
+            String::from_utf8(server.received_requests().await.unwrap()[0].body.clone()).unwrap();
+        assert!(!body.contains("name=\"attachment\""));
+        assert!(!body.contains("the end"));
+
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(400).set_body_string(r#"{"status":0}"#))
+            .mount(&server)
+            .await;
+        let url = format!("{}/1/messages.json", server.uri());
+        let error = send_pushover(&pushover, &reqwest::Client::new(), &notification, &url)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            NotificationError::Rejected {
+                channel: "pushover",
+                ..
+            }
+        ));
+    }
+}
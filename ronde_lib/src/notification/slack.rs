@@ -0,0 +1,117 @@
+use super::chat::{color, excerpt};
+use super::{check_response, Notification, NotificationError, Notifier};
+use crate::config::SlackConfig;
+use async_trait::async_trait;
+use serde_json::{json, Value};
+
+#[async_trait]
+impl Notifier for SlackConfig {
+    fn name(&self) -> &'static str {
+        "slack"
+    }
+
+    async fn send(
+        &self,
+        client: &reqwest::Client,
+        notification: &Notification<'_>,
+    ) -> Result<(), NotificationError> {
+        let response = client
+            .post(&self.webhook_url)
+            .json(&payload(self, notification))
+            .send()
+            .await
+            // The URL is a secret
+            .map_err(reqwest::Error::without_url)?;
+        check_response(self.name(), response).await
+    }
+}
+
+/// Build the message, with a colored attachment
+fn payload(config: &SlackConfig, notification: &Notification<'_>) -> Value {
+    let mut fields = vec![json!({
+        "title": "Command",
+        "value": notification.command_name,
+        "short": true,
+    })];
+    if let Some(exit) = notification.exit() {
+        fields.push(json!({"title": "Exit code", "value": exit.to_string(), "short": true}));
+    }
+    let mut attachment = json!({
+        "fallback": notification.title,
+        "color": format!("#{:06x}", color(notification.notification_type)),
+        "title": notification.title,
+        "fields": fields,
+        "mrkdwn_in": ["text"],
+    });
+    if let Some(excerpt) = excerpt(notification) {
+        attachment["text"] = json!(format!("```\n{excerpt}\n```"));
+    }
+    if let Some(url) = notification.status_page {
+        attachment["title_link"] = json!(url);
+    }
+    let mut payload = json!({
+        "text": notification.title,
+        "attachments": [attachment],
+    });
+    if let Some(ref channel) = config.channel {
+        payload["channel"] = json!(channel);
+    }
+    if let Some(ref username) = config.username {
+        payload["username"] = json!(username);
+    }
+    payload
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notification::tests::failed_entry;
+    use crate::notification::NotificationType;
+    use wiremock::matchers::{body_json, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[tokio::test]
+    async fn test_send() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/hooks/T000/B000/XXXX"))
+            .and(body_json(json!({
+                "text": "New Failure of disk",
+                "channel": "#ops",
+                "attachments": [{
+                    "fallback": "New Failure of disk",
+                    "color": "#d50200",
+                    "title": "New Failure of disk",
+                    "title_link": "https://status.example.com",
+                    "text": "```\ndisk full\n```",
+                    "fields": [
+                        {"title": "Command", "value": "disk", "short": true},
+                        {"title": "Exit code", "value": "1", "short": true},
+                    ],
+                    "mrkdwn_in": ["text"],
+                }],
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_string("ok"))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let config = SlackConfig {
+            webhook_url: format!("{}/hooks/T000/B000/XXXX", server.uri()),
+            channel: Some("#ops".to_string()),
+            username: None,
+        };
+        let entry = failed_entry(1, "disk full\n");
+        let mut notification =
+            Notification::new("disk", None, NotificationType::Failure, Some(&entry));
+        notification.status_page = Some("https://status.example.com");
+        config
+            .send(&reqwest::Client::new(), &notification)
+            .await
+            .unwrap();
+
+        let notification = Notification::new("disk", None, NotificationType::BackFromFailure, None);
+        let payload = payload(&config, &notification);
+        assert_eq!(payload["attachments"][0]["color"], "#2eb886");
+        assert_eq!(payload["attachments"][0].get("text"), None);
+    }
+}
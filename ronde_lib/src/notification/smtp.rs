@@ -0,0 +1,189 @@
+use super::{Notification, NotificationError, Notifier};
+use crate::config::{SmtpConfig, SmtpOutput, SmtpTls};
+use crate::template;
+use async_trait::async_trait;
+use lettre::message::header::ContentType;
+use lettre::message::{Attachment, Mailbox, MultiPart, SinglePart};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+
+/// Default body when the output is attached
+const ATTACHMENT_BODY: &str = "{{title}}\n\nCommand: {{command_line}}\nExit: {{exit}}\n";
+
+#[async_trait]
+impl Notifier for SmtpConfig {
+    fn name(&self) -> &'static str {
+        "smtp"
+    }
+
+    async fn send(
+        &self,
+        _client: &reqwest::Client,
+        notification: &Notification<'_>,
+    ) -> Result<(), NotificationError> {
+        let message = build_message(self, notification)?;
+        let builder = match self.tls {
+            SmtpTls::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&self.host),
+            SmtpTls::Starttls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&self.host)?,
+            SmtpTls::Implicit => AsyncSmtpTransport::<Tokio1Executor>::relay(&self.host)?,
+        };
+        let mut builder = builder.port(self.get_port());
+        if let Some(ref username) = self.username {
+            builder =
+                builder.credentials(Credentials::new(username.clone(), self.password.clone()));
+        }
+        builder.build().send(message).await?;
+        Ok(())
+    }
+}
+
+/// Parse an email address
+fn mailbox(address: &str) -> Result<Mailbox, NotificationError> {
+    address
+        .parse()
+        .map_err(|e| NotificationError::EmailError(format!("invalid address {address}: {e}")))
+}
+
+/// Build the email of a notification
+fn build_message(
+    config: &SmtpConfig,
+    notification: &Notification<'_>,
+) -> Result<Message, NotificationError> {
+    let vars = notification.text_variables();
+    let subject = template::substitute(config.subject.as_deref().unwrap_or("{{title}}"), &vars)?;
+    let (stdout, stderr) = (notification.stdout(), notification.stderr());
+    let attach =
+        config.output == SmtpOutput::Attachment && !(stdout.is_empty() && stderr.is_empty());
+    let default_body = if attach {
+        ATTACHMENT_BODY
+    } else {
+        "{{details}}"
+    };
+    let body = template::substitute(config.body.as_deref().unwrap_or(default_body), &vars)?;
+
+    let mut builder = Message::builder()
+        .from(mailbox(&config.from)?)
+        .subject(subject);
+    for to in config.to.iter() {
+        builder = builder.to(mailbox(to)?);
+    }
+    let message = if attach {
+        let output = format!(">>>STDERR\n{stderr}\n>>>STDOUT\n{stdout}");
+        builder.multipart(
+            MultiPart::mixed()
+                .singlepart(SinglePart::plain(body))
+                .singlepart(
+                    Attachment::new("output.txt".to_string()).body(output, ContentType::TEXT_PLAIN),
+                ),
+        )
+    } else {
+        builder.singlepart(SinglePart::plain(body))
+    };
+    message.map_err(|e| NotificationError::EmailError(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notification::tests::failed_entry;
+    use crate::notification::NotificationType;
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpListener;
+
+    /// Accept a single SMTP session and return the data of the email
+    async fn smtp_sink(listener: TcpListener) -> String {
+        let (stream, _) = listener.accept().await.unwrap();
+        let (reader, mut writer) = stream.into_split();
+        let mut lines = BufReader::new(reader).lines();
+        writer.write_all(b"220 sink ESMTP\r\n").await.unwrap();
+        let mut data = String::new();
+        let mut in_data = false;
+        while let Some(line) = lines.next_line().await.unwrap() {
+            if in_data {
+                if line == "." {
+                    in_data = false;
+                    writer.write_all(b"250 queued\r\n").await.unwrap();
+                } else {
+                    data.push_str(&line);
+                    data.push('\n');
+                }
+                continue;
+            }
+            let reply: &[u8] = match line.split(' ').next().unwrap().to_uppercase().as_str() {
+                "DATA" => {
+                    in_data = true;
+                    b"354 go ahead\r\n"
+                }
+                "QUIT" => {
+                    writer.write_all(b"221 bye\r\n").await.unwrap();
+                    break;
+                }
+                _ => b"250 ok\r\n",
+            };
+            writer.write_all(reply).await.unwrap();
+        }
+        data
+    }
+
+    fn config(port: u16) -> SmtpConfig {
+        SmtpConfig {
+            host: "127.0.0.1".to_string(),
+            port: Some(port),
+            tls: SmtpTls::None,
+            from: "Ronde <ronde@example.com>".to_string(),
+            to: vec![
+                "oncall@example.com".to_string(),
+                "ops@example.com".to_string(),
+            ],
+            subject: Some("[ronde] {{title}}".to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_send() {
+        let entry = failed_entry(1, "disk full\n");
+        let notification = Notification::new("disk", None, NotificationType::Failure, Some(&entry));
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let sink = tokio::spawn(smtp_sink(listener));
+        config(port)
+            .send(&reqwest::Client::new(), &notification)
+            .await
+            .unwrap();
+        let data = sink.await.unwrap();
+        assert!(data.contains("Subject: [ronde] New Failure of disk"));
+        assert!(data.contains("To: oncall@example.com, ops@example.com"));
+        assert!(data.contains("stderr: disk full"));
+        assert!(!data.contains("output.txt"));
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let sink = tokio::spawn(smtp_sink(listener));
+        let config = SmtpConfig {
+            output: SmtpOutput::Attachment,
+            ..config(port)
+        };
+        config
+            .send(&reqwest::Client::new(), &notification)
+            .await
+            .unwrap();
+        let data = sink.await.unwrap();
+        assert!(data.contains("Content-Disposition: attachment; filename=\"output.txt\""));
+        assert!(data.contains("Command: df -h\nExit: 1\n"));
+    }
+
+    #[test]
+    fn test_invalid_address() {
+        let notification = Notification::new("disk", None, NotificationType::Test, None);
+        let config = SmtpConfig {
+            from: "not an address".to_string(),
+            ..config(25)
+        };
+        assert!(matches!(
+            build_message(&config, &notification),
+            Err(NotificationError::EmailError(_))
+        ));
+    }
+}
@@ -0,0 +1,165 @@
+use super::chat::excerpt;
+use super::{check_response, Notification, NotificationError, NotificationType, Notifier};
+use crate::config::TelegramConfig;
+use async_trait::async_trait;
+use serde_json::{json, Value};
+
+/// Default URL of the Bot API
+const DEFAULT_API_URL: &str = "https://api.telegram.org";
+
+#[async_trait]
+impl Notifier for TelegramConfig {
+    fn name(&self) -> &'static str {
+        "telegram"
+    }
+
+    async fn send(
+        &self,
+        client: &reqwest::Client,
+        notification: &Notification<'_>,
+    ) -> Result<(), NotificationError> {
+        let api_url = self.api_url.as_deref().unwrap_or(DEFAULT_API_URL);
+        let url = format!(
+            "{}/bot{}/sendMessage",
+            api_url.trim_end_matches('/'),
+            self.bot_token
+        );
+        let response = client
+            .post(url)
+            .json(&payload(self, notification))
+            .send()
+            .await
+            // The URL holds the token of the bot
+            .map_err(reqwest::Error::without_url)?;
+        check_response(self.name(), response).await
+    }
+}
+
+/// Escape text for MarkdownV2
+fn escape(text: &str) -> String {
+    escape_chars(text, "_*[]()~`>#+-=|{}.!\\")
+}
+
+/// Escape the given characters with a backslash
+fn escape_chars(text: &str, special: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if special.contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Build the message, formatted with MarkdownV2
+fn payload(config: &TelegramConfig, notification: &Notification<'_>) -> Value {
+    let mut text = format!("*{}*", escape(&notification.title));
+    if let Some(excerpt) = excerpt(notification) {
+        // Only ` and \ have to be escaped in code blocks
+        text.push_str(&format!("\n```\n{}\n```", escape_chars(&excerpt, "`\\")));
+    }
+    if let Some(url) = notification.status_page {
+        text.push_str(&format!("\n[Status page]({})", escape_chars(url, ")\\")));
+    }
+    let mut payload = json!({
+        "chat_id": config.chat_id,
+        "text": text,
+        "parse_mode": "MarkdownV2",
+        "link_preview_options": {"is_disabled": true},
+    });
+    if let Some(thread) = config.message_thread_id {
+        payload["message_thread_id"] = json!(thread);
+    }
+    // Reminders should not wake anybody up
+    if notification.notification_type == NotificationType::ContinuousFailure {
+        payload["disable_notification"] = json!(true);
+    }
+    payload
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::TelegramChatId;
+    use crate::notification::tests::failed_entry;
+    use wiremock::matchers::{body_json, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(
+            escape("disk-1 (sda.2) is 100%!"),
+            r"disk\-1 \(sda\.2\) is 100%\!"
+        );
+        assert_eq!(escape(r"a_b*c\d"), r"a\_b\*c\\d");
+    }
+
+    #[tokio::test]
+    async fn test_send() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/bot123:ABC/sendMessage"))
+            .and(body_json(json!({
+                "chat_id": -1001234,
+                "message_thread_id": 42,
+                "text": "*Continuous failure of disk\\.root*\n```\nfull \\`/\\` \\\\o/\n```\n[Status page](https://status.example.com/#a\\)b)",
+                "parse_mode": "MarkdownV2",
+                "link_preview_options": {"is_disabled": true},
+                "disable_notification": true,
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({"ok": true})))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let config = TelegramConfig {
+            bot_token: "123:ABC".to_string(),
+            bot_token_file: None,
+            chat_id: TelegramChatId::Id(-1001234),
+            message_thread_id: Some(42),
+            api_url: Some(server.uri()),
+        };
+        let entry = failed_entry(1, "full `/` \\o/\n");
+        let mut notification = Notification::new(
+            "disk.root",
+            None,
+            NotificationType::ContinuousFailure,
+            Some(&entry),
+        );
+        notification.status_page = Some("https://status.example.com/#a)b");
+        config
+            .send(&reqwest::Client::new(), &notification)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_rejected() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(
+                ResponseTemplate::new(400).set_body_json(
+                    json!({"ok": false, "description": "Bad Request: chat not found"}),
+                ),
+            )
+            .mount(&server)
+            .await;
+        let config = TelegramConfig {
+            bot_token: "123:ABC".to_string(),
+            bot_token_file: None,
+            chat_id: TelegramChatId::Username("@ronde".to_string()),
+            message_thread_id: None,
+            api_url: Some(server.uri()),
+        };
+        let notification = Notification::new("disk", None, NotificationType::Failure, None);
+        assert_eq!(
+            payload(&config, &notification).get("disable_notification"),
+            None
+        );
+        let err = config
+            .send(&reqwest::Client::new(), &notification)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("chat not found"));
+    }
+}
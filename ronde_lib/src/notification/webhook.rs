@@ -0,0 +1,153 @@
+use super::{check_response, Notification, NotificationError, Notifier};
+use crate::config::WebhookConfig;
+use crate::template;
+use async_trait::async_trait;
+use reqwest::header::CONTENT_TYPE;
+use reqwest::Method;
+use serde_json::Value;
+use std::collections::HashMap;
+
+#[async_trait]
+impl Notifier for WebhookConfig {
+    fn name(&self) -> &'static str {
+        "webhook"
+    }
+
+    async fn send(
+        &self,
+        client: &reqwest::Client,
+        notification: &Notification<'_>,
+    ) -> Result<(), NotificationError> {
+        let body = render_body(self, notification)?;
+        let method = self.method.as_deref().unwrap_or("POST");
+        let method = Method::from_bytes(method.to_uppercase().as_bytes())
+            .map_err(|e| NotificationError::InvalidRequest(format!("{method}: {e}")))?;
+        let mut request = client
+            .request(method, &self.url)
+            .header(CONTENT_TYPE, "application/json")
+            .body(body);
+        for (name, value) in self.headers.iter() {
+            request = request.header(name, value);
+        }
+        let response = request
+            .send()
+            .await
+            // The URL may hold interpolated secrets
+            .map_err(reqwest::Error::without_url)?;
+        check_response(self.name(), response).await
+    }
+}
+
+/// Render the body of the request
+///
+/// Strings are substituted without their quotes, so that `"{{stderr}}"`
+/// is a valid JSON string. Other values, like `{{exit}}`, are substituted
+/// as is, `null` when unknown.
+fn render_body(
+    config: &WebhookConfig,
+    notification: &Notification<'_>,
+) -> Result<String, NotificationError> {
+    let variables = notification.variables();
+    let Some(ref body) = config.body else {
+        let object: serde_json::Map<String, Value> = variables
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v))
+            .collect();
+        return Ok(Value::Object(object).to_string());
+    };
+    let vars: HashMap<String, String> = variables
+        .into_iter()
+        .map(|(k, v)| {
+            let value = match v {
+                Value::String(s) => {
+                    let quoted = Value::String(s).to_string();
+                    quoted[1..quoted.len() - 1].to_string()
+                }
+                Value::Null => "null".to_string(),
+                v => v.to_string(),
+            };
+            (k.to_string(), value)
+        })
+        .collect();
+    let body = template::substitute(body, &vars)?;
+    // Catch templates producing invalid JSON before the server does
+    serde_json::from_str::<Value>(&body)?;
+    Ok(body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notification::tests::failed_entry;
+    use crate::notification::NotificationType;
+    use serde_json::json;
+    use wiremock::matchers::{body_json, header, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[test]
+    fn test_render_body() {
+        let entry = failed_entry(2, "disk \"/\" is full\n");
+        let notification = Notification::new("disk", None, NotificationType::Failure, Some(&entry));
+        let mut config = WebhookConfig::default();
+        let body: Value =
+            serde_json::from_str(&render_body(&config, &notification).unwrap()).unwrap();
+        assert_eq!(body["name"], "disk");
+        assert_eq!(body["type"], "failure");
+        assert_eq!(body["exit"], 2);
+        assert_eq!(body["severity"], Value::Null);
+
+        config.body = Some(r#"{"text": "{{title}}: {{stderr}}", "code": {{exit}}}"#.to_string());
+        assert_eq!(
+            render_body(&config, &notification).unwrap(),
+            r#"{"text": "New Failure of disk: disk \"/\" is full\n", "code": 2}"#
+        );
+
+        config.body = Some(r#"{"text": {{title}}}"#.to_string());
+        assert!(matches!(
+            render_body(&config, &notification),
+            Err(NotificationError::InvalidPayload(_))
+        ));
+        config.body = Some(r#"{"text": "{{unknown}}"}"#.to_string());
+        assert!(matches!(
+            render_body(&config, &notification),
+            Err(NotificationError::TemplateError(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_send() {
+        let server = MockServer::start().await;
+        Mock::given(method("PUT"))
+            .and(path("/hook"))
+            .and(header("X-Token", "s3cr3t"))
+            .and(header("Content-Type", "application/json"))
+            .and(body_json(json!({"text": "Back from failure on disk"})))
+            .respond_with(ResponseTemplate::new(204))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let entry = failed_entry(2, "disk \"/\" is full\n");
+        let notification = Notification::new(
+            "disk",
+            None,
+            NotificationType::BackFromFailure,
+            Some(&entry),
+        );
+        let mut config = WebhookConfig {
+            url: format!("{}/hook", server.uri()),
+            method: Some("put".to_string()),
+            headers: HashMap::from([("X-Token".to_string(), "s3cr3t".to_string())]),
+            body: Some(r#"{"text": "{{title}}"}"#.to_string()),
+        };
+        config
+            .send(&reqwest::Client::new(), &notification)
+            .await
+            .unwrap();
+
+        config.url = format!("{}/other", server.uri());
+        assert!(matches!(
+            config.send(&reqwest::Client::new(), &notification).await,
+            Err(NotificationError::Rejected { status, .. }) if status == 404
+        ));
+    }
+}
@@ -0,0 +1,351 @@
+use crate::config::Severity;
+use crate::history::{redacted_result, HistoryItemError};
+use crate::runner::{CommandOutput, CommandResult, ResourceUsage};
+use crate::secrets::Secrets;
+use crate::summary::Summary;
+use serde_derive::Serialize;
+
+/// Format of the report of a run
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutputFormat {
+    /// JSON document with the summary and the results
+    Json,
+    /// Test Anything Protocol
+    Tap,
+    /// JUnit XML
+    Junit,
+}
+
+impl std::str::FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(OutputFormat::Json),
+            "tap" => Ok(OutputFormat::Tap),
+            "junit" => Ok(OutputFormat::Junit),
+            _ => Err(format!("unknown output format {s}")),
+        }
+    }
+}
+
+/// Overall outcome of a run
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Outcome {
+    /// Every command succeeded
+    Ok,
+    /// Only commands whose severity is `warning` failed
+    Warning,
+    /// At least one critical command failed
+    Failure,
+}
+
+impl Outcome {
+    /// Get the outcome of the failure of a command
+    pub fn from_severity(severity: Severity) -> Self {
+        match severity {
+            Severity::Warning => Outcome::Warning,
+            Severity::Critical => Outcome::Failure,
+        }
+    }
+
+    /// Get the outcome of a run
+    pub fn from_results(results: &[CommandResult]) -> Self {
+        results
+            .iter()
+            .filter(|r| r.result.is_err())
+            .map(|r| Outcome::from_severity(r.config.severity))
+            .max()
+            .unwrap_or(Outcome::Ok)
+    }
+
+    /// Exit code of ronde: 0 when all ok, 1 on warnings, 2 on failures
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            Outcome::Ok => 0,
+            Outcome::Warning => 1,
+            Outcome::Failure => 2,
+        }
+    }
+}
+
+/// Result of a command, as reported
+#[derive(Debug, Serialize)]
+struct ReportEntry<'a> {
+    /// Name of the command
+    name: &'a str,
+    /// Command line
+    command: String,
+    /// Severity of a failure
+    severity: Severity,
+    /// Result of the command
+    result: Result<CommandOutput, HistoryItemError>,
+    /// Resource usage of the command
+    #[serde(skip_serializing_if = "Option::is_none")]
+    usage: Option<&'a ResourceUsage>,
+}
+
+/// Report of a run
+#[derive(Debug, Serialize)]
+struct Report<'a> {
+    /// Summary of the run
+    summary: &'a Summary,
+    /// Results of the commands
+    results: Vec<ReportEntry<'a>>,
+}
+
+impl<'a> Report<'a> {
+    fn new(results: &'a [CommandResult], summary: &'a Summary, secrets: &Secrets) -> Self {
+        Report {
+            summary,
+            results: results
+                .iter()
+                .map(|r| ReportEntry {
+                    name: &r.config.name,
+                    command: secrets.redact(&r.config.command_line()),
+                    severity: r.config.severity,
+                    result: redacted_result(r, secrets),
+                    usage: r.usage.as_ref(),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Get the outputs of a result
+fn outputs(result: &Result<CommandOutput, HistoryItemError>) -> (&str, &str) {
+    match result {
+        Ok(output) => (&output.stdout, &output.stderr),
+        Err(HistoryItemError::CommandError { stdout, stderr, .. }) => (stdout, stderr),
+        Err(_) => ("", ""),
+    }
+}
+
+/// Short description of a failure
+fn failure_message(error: &HistoryItemError) -> String {
+    match error {
+        HistoryItemError::Timeout { timeout } => format!("timed out after {timeout}s"),
+        HistoryItemError::CommandError { exit, .. } => format!("exited with {exit}"),
+        HistoryItemError::Other { message } => message.clone(),
+    }
+}
+
+/// Write `value` as a YAML literal block
+fn yaml_block(key: &str, value: &str, out: &mut String) {
+    if value.is_empty() {
+        return;
+    }
+    out.push_str(&format!("  {key}: |\n"));
+    for line in value.lines() {
+        out.push_str(&format!("    {line}\n"));
+    }
+}
+
+/// Render the report in TAP version 13
+fn render_tap(report: &Report) -> String {
+    let mut out = format!("TAP version 13\n1..{}\n", report.results.len());
+    for (idx, entry) in report.results.iter().enumerate() {
+        let name = entry.name.replace('#', "\\#");
+        match entry.result {
+            Ok(_) => out.push_str(&format!("ok {} - {}\n", idx + 1, name)),
+            Err(ref e) => {
+                out.push_str(&format!("not ok {} - {}\n", idx + 1, name));
+                out.push_str("  ---\n");
+                out.push_str(&format!(
+                    "  message: {}\n",
+                    serde_json::Value::String(failure_message(e))
+                ));
+                out.push_str(&format!("  severity: {}\n", entry.severity));
+                out.push_str(&format!(
+                    "  command: {}\n",
+                    serde_json::Value::String(entry.command.clone())
+                ));
+                let (stdout, stderr) = outputs(&entry.result);
+                yaml_block("stdout", stdout, &mut out);
+                yaml_block("stderr", stderr, &mut out);
+                out.push_str("  ...\n");
+            }
+        }
+    }
+    out.push_str(&format!(
+        "# ok {}, failed {}\n",
+        report.summary.nb_ok, report.summary.nb_err
+    ));
+    out
+}
+
+/// Escape a string for XML
+pub(crate) fn xml_escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            // Not allowed in XML 1.0
+            c if (c as u32) < 0x20 && !matches!(c, '\t' | '\n' | '\r') => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Render the report as JUnit XML
+fn render_junit(report: &Report, name: &str) -> String {
+    let tests = report.results.len();
+    let failures = report.summary.nb_err;
+    let name = xml_escape(name);
+    let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    out.push_str(&format!(
+        "<testsuites name=\"{name}\" tests=\"{tests}\" failures=\"{failures}\">\n"
+    ));
+    out.push_str(&format!(
+        "  <testsuite name=\"{name}\" tests=\"{tests}\" failures=\"{failures}\" errors=\"0\">\n"
+    ));
+    for entry in report.results.iter() {
+        out.push_str(&format!(
+            "    <testcase name=\"{}\" classname=\"{}\">\n",
+            xml_escape(entry.name),
+            name
+        ));
+        if let Err(ref e) = entry.result {
+            out.push_str(&format!(
+                "      <failure message=\"{}\" type=\"{}\">{}</failure>\n",
+                xml_escape(&failure_message(e)),
+                entry.severity,
+                xml_escape(&entry.command)
+            ));
+        }
+        let (stdout, stderr) = outputs(&entry.result);
+        if !stdout.is_empty() {
+            out.push_str(&format!(
+                "      <system-out>{}</system-out>\n",
+                xml_escape(stdout)
+            ));
+        }
+        if !stderr.is_empty() {
+            out.push_str(&format!(
+                "      <system-err>{}</system-err>\n",
+                xml_escape(stderr)
+            ));
+        }
+        out.push_str("    </testcase>\n");
+    }
+    out.push_str("  </testsuite>\n</testsuites>\n");
+    out
+}
+
+/// Render the results of a run and its summary
+///
+/// `name` is the name of the site, used as the name of the JUnit test
+/// suite. Secret values are redacted from the command lines and outputs.
+pub fn render(
+    format: OutputFormat,
+    name: &str,
+    results: &[CommandResult],
+    summary: &Summary,
+    secrets: &Secrets,
+) -> String {
+    let report = Report::new(results, summary, secrets);
+    match format {
+        OutputFormat::Json => {
+            serde_json::to_string_pretty(&report).expect("report is serializable") + "\n"
+        }
+        OutputFormat::Tap => render_tap(&report),
+        OutputFormat::Junit => render_junit(&report, name),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::CommandConfig;
+    use crate::runner::CommandError;
+
+    fn results() -> Vec<CommandResult> {
+        let ok = CommandConfig {
+            name: "ok".to_string(),
+            run: Some("true".into()),
+            ..Default::default()
+        };
+        let warn = CommandConfig {
+            name: "disk #1".to_string(),
+            run: Some("df <s3cr3t>".into()),
+            severity: Severity::Warning,
+            ..Default::default()
+        };
+        vec![
+            CommandResult::ok(
+                ok,
+                CommandOutput {
+                    exit: 0,
+                    stdout: "fine\n".to_string(),
+                    stderr: String::new(),
+                },
+            ),
+            CommandResult::error(
+                warn,
+                CommandError::Command(std::io::Error::other("no such file")),
+            ),
+        ]
+    }
+
+    #[test]
+    fn test_outcome() {
+        let mut results = results();
+        assert_eq!(Outcome::from_results(&results[..1]), Outcome::Ok);
+        assert_eq!(Outcome::from_results(&results), Outcome::Warning);
+        results[1].config.severity = Severity::Critical;
+        assert_eq!(Outcome::from_results(&results), Outcome::Failure);
+        assert_eq!(Outcome::Failure.exit_code(), 2);
+    }
+
+    #[test]
+    fn test_render() {
+        let results = results();
+        let summary = Summary::from_results(&results);
+        let mut secrets = Secrets::default();
+        secrets.add("s3cr3t");
+
+        let json = render(OutputFormat::Json, "Ronde", &results, &summary, &secrets);
+        let json: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(json["summary"]["nb_err"], 1);
+        assert_eq!(json["results"][0]["result"]["Ok"]["stdout"], "fine\n");
+        assert_eq!(json["results"][1]["severity"], "warning");
+        assert_eq!(json["results"][1]["command"], "df <********>");
+
+        assert_eq!(
+            render(OutputFormat::Tap, "Ronde", &results, &summary, &secrets),
+            r#"TAP version 13
+1..2
+ok 1 - ok
+not ok 2 - disk \#1
+  ---
+  message: "Command error: no such file"
+  severity: warning
+  command: "df <********>"
+  ...
+# ok 1, failed 1
+"#
+        );
+
+        assert_eq!(
+            render(OutputFormat::Junit, "Ronde", &results, &summary, &secrets),
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<testsuites name="Ronde" tests="2" failures="1">
+  <testsuite name="Ronde" tests="2" failures="1" errors="0">
+    <testcase name="ok" classname="Ronde">
+      <system-out>fine
+</system-out>
+    </testcase>
+    <testcase name="disk #1" classname="Ronde">
+      <failure message="Command error: no such file" type="warning">df &lt;********&gt;</failure>
+    </testcase>
+  </testsuite>
+</testsuites>
+"#
+        );
+    }
+}
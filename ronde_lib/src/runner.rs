@@ -1,9 +1,16 @@
-use crate::config::{CommandConfig, DefaultRunnerEnv};
+use crate::config::{CommandConfig, DefaultRunnerEnv, Run};
+use crate::sandbox::{Sandbox, SandboxError};
 use serde_derive::{Deserialize, Serialize};
-use std::process::Output;
-use std::time::Duration;
+use std::fs::File;
+use std::io::{self, Write};
+use std::os::fd::{AsRawFd, FromRawFd};
+use std::os::unix::process::{CommandExt, ExitStatusExt};
+use std::process::{Child, Command, ExitStatus, Output};
+use std::time::{Duration, Instant};
 use thiserror::Error;
-use tokio::process::Command;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::process::{ChildStderr, ChildStdin, ChildStdout};
+use tracing::{info, warn};
 
 /// Command output
 #[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
@@ -36,6 +43,50 @@ impl std::fmt::Display for CommandOutput {
     }
 }
 
+/// Resource usage of a command, as reported by `wait4(2)`
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct ResourceUsage {
+    /// User CPU time, in microseconds
+    pub user_time: u64,
+    /// System CPU time, in microseconds
+    pub system_time: u64,
+    /// Maximum resident set size, in kilobytes
+    pub max_rss: u64,
+    /// Number of voluntary context switches
+    pub voluntary_switches: u64,
+    /// Number of involuntary context switches
+    pub involuntary_switches: u64,
+}
+
+impl From<libc::rusage> for ResourceUsage {
+    fn from(rusage: libc::rusage) -> Self {
+        fn micros(tv: libc::timeval) -> u64 {
+            (tv.tv_sec as u64) * 1_000_000 + tv.tv_usec as u64
+        }
+        ResourceUsage {
+            user_time: micros(rusage.ru_utime),
+            system_time: micros(rusage.ru_stime),
+            max_rss: rusage.ru_maxrss as u64,
+            voluntary_switches: rusage.ru_nvcsw as u64,
+            involuntary_switches: rusage.ru_nivcsw as u64,
+        }
+    }
+}
+
+impl std::fmt::Display for ResourceUsage {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "user: {:.3}s, sys: {:.3}s, max rss: {}kB, ctx switches: {} voluntary, {} involuntary",
+            self.user_time as f64 / 1_000_000.0,
+            self.system_time as f64 / 1_000_000.0,
+            self.max_rss,
+            self.voluntary_switches,
+            self.involuntary_switches
+        )
+    }
+}
+
 /// Command returned an error
 #[derive(Error, Debug)]
 pub struct ReturnedError {
@@ -65,6 +116,9 @@ pub enum CommandError {
     /// Returned error
     #[error("Returned error: {0}")]
     ReturnedError(#[from] ReturnedError),
+    /// Sandbox error
+    #[error("Sandbox error: {0}")]
+    Sandbox(#[from] SandboxError),
 }
 
 /// Command result
@@ -74,6 +128,8 @@ pub struct CommandResult {
     pub config: CommandConfig,
     /// Result of the command
     pub result: Result<CommandOutput, CommandError>,
+    /// Resource usage of the command, if it could be collected
+    pub usage: Option<ResourceUsage>,
 }
 
 impl CommandResult {
@@ -82,6 +138,7 @@ impl CommandResult {
         CommandResult {
             config,
             result: Err(error),
+            usage: None,
         }
     }
     /// Create a new CommandResult with an Ok result
@@ -89,52 +146,666 @@ impl CommandResult {
         CommandResult {
             config,
             result: Ok(output),
+            usage: None,
         }
     }
 }
 
+/// Block until the process `pid` has exited, without reaping it
+///
+/// The process stays a zombie so that its pid cannot be reused before
+/// `reap` is called, which makes it safe to kill it in the meantime.
+fn wait_exited(pid: libc::pid_t) -> io::Result<()> {
+    loop {
+        let mut info: libc::siginfo_t = unsafe { std::mem::zeroed() };
+        let ret = unsafe {
+            libc::waitid(
+                libc::P_PID,
+                pid as libc::id_t,
+                &mut info,
+                libc::WEXITED | libc::WNOWAIT,
+            )
+        };
+        if ret == 0 {
+            return Ok(());
+        }
+        let err = io::Error::last_os_error();
+        if err.kind() != io::ErrorKind::Interrupted {
+            return Err(err);
+        }
+    }
+}
+
+/// Reap the process `pid` and collect its resource usage
+fn reap(pid: libc::pid_t) -> io::Result<(ExitStatus, ResourceUsage)> {
+    loop {
+        let mut status: libc::c_int = 0;
+        let mut rusage: libc::rusage = unsafe { std::mem::zeroed() };
+        let ret = unsafe { libc::wait4(pid, &mut status, 0, &mut rusage) };
+        if ret == pid {
+            return Ok((ExitStatus::from_raw(status), rusage.into()));
+        }
+        let err = io::Error::last_os_error();
+        if err.kind() != io::ErrorKind::Interrupted {
+            return Err(err);
+        }
+    }
+}
+
+/// Kill and reap a child whose wait is cancelled, like `kill_on_drop` does
+/// for the children of tokio
+struct KillOnDrop {
+    /// Process to kill
+    pid: libc::pid_t,
+    /// Whether the process still has to be killed when dropped
+    armed: bool,
+}
+
+impl Drop for KillOnDrop {
+    fn drop(&mut self) {
+        if self.armed {
+            // The child has not been reaped yet, so its pid is still ours
+            unsafe { libc::kill(self.pid, libc::SIGKILL) };
+            let pid = self.pid;
+            std::thread::spawn(move || reap(pid));
+        }
+    }
+}
+
+/// Output captured from a pipe
+///
+/// When over the size limit, only the head and the tail of the output are
+/// kept, each being half of the limit.
+struct Capture {
+    /// Maximum number of bytes to keep
+    limit: Option<usize>,
+    /// Captured bytes
+    data: Vec<u8>,
+    /// Number of bytes dropped from the middle of the output
+    truncated: usize,
+}
+
+impl Capture {
+    fn new(limit: Option<usize>) -> Self {
+        Capture {
+            limit,
+            data: Vec::new(),
+            truncated: 0,
+        }
+    }
+
+    /// Append a chunk of output
+    fn extend(&mut self, chunk: &[u8]) {
+        self.data.extend_from_slice(chunk);
+        // Do not shuffle bytes around on every chunk
+        if let Some(limit) = self.limit {
+            if self.data.len() > 2 * limit.max(4096) {
+                self.compact(limit);
+            }
+        }
+    }
+
+    /// Drop the bytes between the head and the tail
+    fn compact(&mut self, limit: usize) {
+        if self.data.len() > limit {
+            let head = limit / 2;
+            let excess = self.data.len() - limit;
+            self.data.drain(head..head + excess);
+            self.truncated += excess;
+        }
+    }
+
+    /// Get the captured output, with a marker where bytes were dropped
+    fn finish(mut self) -> Vec<u8> {
+        if let Some(limit) = self.limit {
+            self.compact(limit);
+            if self.truncated > 0 {
+                // Do not cut UTF-8 characters in two
+                let head = limit / 2;
+                let start = floor_char_boundary(&self.data[..head]);
+                let end = head
+                    + self.data[head..]
+                        .iter()
+                        .take(3)
+                        .take_while(|&&b| is_continuation(b))
+                        .count();
+                self.truncated += end - start;
+                let marker = format!("\n[... {} bytes truncated ...]\n", self.truncated);
+                self.data.splice(start..end, marker.into_bytes());
+            }
+        }
+        self.data
+    }
+}
+
+/// Whether `byte` continues a UTF-8 character
+fn is_continuation(byte: u8) -> bool {
+    byte & 0xC0 == 0x80
+}
+
+/// Get the length of `bytes` without the UTF-8 character cut at its end, if
+/// any
+fn floor_char_boundary(bytes: &[u8]) -> usize {
+    // A character has at most 3 continuation bytes
+    for (back, &byte) in bytes.iter().rev().take(4).enumerate() {
+        if !is_continuation(byte) {
+            let start = bytes.len() - back - 1;
+            let width = match byte {
+                0xC0..=0xDF => 2,
+                0xE0..=0xEF => 3,
+                0xF0..=0xF7 => 4,
+                _ => 1,
+            };
+            return if start + width > bytes.len() {
+                start
+            } else {
+                bytes.len()
+            };
+        }
+    }
+    bytes.len()
+}
+
+/// Read a pipe of the child until EOF, keeping at most `limit` bytes
+async fn read_pipe<R: AsyncRead + Unpin>(
+    pipe: Option<R>,
+    limit: Option<usize>,
+) -> io::Result<Vec<u8>> {
+    let mut capture = Capture::new(limit);
+    if let Some(mut pipe) = pipe {
+        let mut chunk = [0u8; 8192];
+        loop {
+            let n = pipe.read(&mut chunk).await?;
+            if n == 0 {
+                break;
+            }
+            capture.extend(&chunk[..n]);
+        }
+    }
+    Ok(capture.finish())
+}
+
+/// Write the input to the stdin of the child, then close it
+async fn write_pipe<W: AsyncWrite + Unpin>(
+    pipe: Option<W>,
+    input: Option<Vec<u8>>,
+) -> io::Result<()> {
+    if let (Some(mut pipe), Some(input)) = (pipe, input) {
+        match pipe.write_all(&input).await {
+            // The child does not care about its input
+            Err(e) if e.kind() == io::ErrorKind::BrokenPipe => {}
+            result => result?,
+        }
+    }
+    Ok(())
+}
+
+/// Wait for a child to finish, collecting its output and resource usage
+///
+/// The child is killed if it does not finish before `timeout`. Each of
+/// stdout and stderr is truncated to `limit` bytes.
+async fn wait_child(
+    mut child: Child,
+    input: Option<Vec<u8>>,
+    limit: Option<usize>,
+    timeout: Duration,
+) -> (Result<Output, CommandError>, Option<ResourceUsage>) {
+    let pid = child.id() as libc::pid_t;
+    let mut guard = KillOnDrop { pid, armed: true };
+    let run = async {
+        let stdin = child.stdin.take().map(ChildStdin::from_std).transpose()?;
+        let stdout = child.stdout.take().map(ChildStdout::from_std).transpose()?;
+        let stderr = child.stderr.take().map(ChildStderr::from_std).transpose()?;
+        let exited = tokio::task::spawn_blocking(move || wait_exited(pid));
+        let (_, stdout, stderr) = tokio::try_join!(
+            write_pipe(stdin, input),
+            read_pipe(stdout, limit),
+            read_pipe(stderr, limit)
+        )?;
+        exited.await.map_err(io::Error::other)??;
+        Ok::<_, io::Error>((stdout, stderr))
+    };
+    let outcome = tokio::time::timeout(timeout, run).await;
+    if !matches!(outcome, Ok(Ok(_))) {
+        // The child has not been reaped yet, so its pid is still ours
+        unsafe { libc::kill(pid, libc::SIGKILL) };
+    }
+    // The child is dead: reaping it goes on even if this is cancelled
+    guard.armed = false;
+    let reaped = tokio::task::spawn_blocking(move || reap(pid))
+        .await
+        .map_err(io::Error::other)
+        .and_then(|r| r);
+    let (status, usage) = match reaped {
+        Ok(reaped) => reaped,
+        Err(e) => return (Err(e.into()), None),
+    };
+    let result = match outcome {
+        Ok(Ok((stdout, stderr))) => {
+            let output = Output {
+                status,
+                stdout,
+                stderr,
+            };
+            if output.status.success() {
+                Ok(output)
+            } else {
+                Err(ReturnedError { output }.into())
+            }
+        }
+        Ok(Err(e)) => Err(e.into()),
+        Err(e) => Err(e.into()),
+    };
+    (result, Some(usage))
+}
+
+/// Write a script into an anonymous in-memory file
+fn script_file(script: &str) -> io::Result<File> {
+    let fd = unsafe { libc::memfd_create(c"ronde-script".as_ptr(), libc::MFD_CLOEXEC) };
+    if fd == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    let mut file = unsafe { File::from_raw_fd(fd) };
+    file.write_all(script.as_bytes())?;
+    Ok(file)
+}
+
+/// Build the process to spawn for a command
+///
+/// When running a script, the file holding it is returned as well and must
+/// be kept open until the process is spawned.
+fn build_command(
+    config: &CommandConfig,
+    defaults: &DefaultRunnerEnv,
+) -> io::Result<(Command, Option<File>)> {
+    match (config.run.as_ref(), config.script.as_ref()) {
+        (Some(Run::Shell(command_line)), _) => {
+            let mut cmd = Command::new(config.get_shell(defaults));
+            cmd.arg("-c").arg(command_line);
+            Ok((cmd, None))
+        }
+        (Some(Run::Argv(argv)), _) => match argv.split_first() {
+            Some((program, args)) => {
+                let mut cmd = Command::new(program);
+                cmd.args(args);
+                Ok((cmd, None))
+            }
+            None => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "empty run array",
+            )),
+        },
+        (None, Some(script)) => {
+            let file = script_file(script)?;
+            let fd = file.as_raw_fd();
+            let interpreter = match config.interpreter {
+                Some(ref interpreter) => interpreter.clone(),
+                None => config.get_shell(defaults),
+            };
+            let mut cmd = Command::new(interpreter);
+            cmd.arg(format!("/dev/fd/{fd}"));
+            // Let the interpreter inherit the script
+            unsafe {
+                cmd.pre_exec(move || {
+                    if libc::fcntl(fd, libc::F_SETFD, 0) == -1 {
+                        return Err(io::Error::last_os_error());
+                    }
+                    Ok(())
+                });
+            }
+            Ok((cmd, Some(file)))
+        }
+        (None, None) => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "nothing to run",
+        )),
+    }
+}
+
+/// Get the data to write to the stdin of the command, if any
+async fn read_input(config: &CommandConfig) -> io::Result<Option<Vec<u8>>> {
+    match (config.stdin.as_ref(), config.stdin_file.as_ref()) {
+        (Some(stdin), _) => Ok(Some(stdin.as_bytes().to_vec())),
+        (None, Some(path)) => match tokio::fs::read(path).await {
+            Ok(input) => Ok(Some(input)),
+            Err(e) => Err(io::Error::new(e.kind(), format!("{path}: {e}"))),
+        },
+        (None, None) => Ok(None),
+    }
+}
+
 /// Execute a command
 pub async fn execute_command(config: CommandConfig, defaults: &DefaultRunnerEnv) -> CommandResult {
-    let mut cmd = Command::new("sh");
+    let name = config.name.clone();
+    info!(command = %name, "starting command");
+    let start = Instant::now();
+    let result = spawn_command(config, defaults).await;
+    let elapsed_ms = start.elapsed().as_millis() as u64;
+    // Outputs are not logged as they may hold secrets
+    match result.result {
+        Ok(ref output) => {
+            info!(command = %name, exit = output.exit, elapsed_ms, "command succeeded")
+        }
+        Err(CommandError::ReturnedError(ref e)) => warn!(
+            command = %name,
+            exit = e.output.status.code().unwrap_or(-1),
+            elapsed_ms,
+            "command failed"
+        ),
+        Err(CommandError::TimedOut(_)) => warn!(command = %name, elapsed_ms, "command timed out"),
+        Err(ref e) => warn!(command = %name, error = %e, elapsed_ms, "command could not run"),
+    }
+    result
+}
+
+/// Spawn a command and wait for it
+async fn spawn_command(config: CommandConfig, defaults: &DefaultRunnerEnv) -> CommandResult {
+    let input = match read_input(&config).await {
+        Ok(input) => input,
+        Err(e) => return CommandResult::error(config, e.into()),
+    };
+    let (mut cmd, script) = match build_command(&config, defaults) {
+        Ok(built) => built,
+        Err(e) => return CommandResult::error(config, e.into()),
+    };
+    // A command waiting on input fails right away instead of hitting the
+    // timeout
+    let stdin = match input {
+        Some(_) => std::process::Stdio::piped(),
+        None => std::process::Stdio::null(),
+    };
     let mut cmd = cmd
-        .arg("-c")
-        .arg(&config.run)
-        .kill_on_drop(true)
+        .stdin(stdin)
         .stdout(std::process::Stdio::piped())
         .stderr(std::process::Stdio::piped());
-    if let Some(uid) = config.get_uid(defaults) {
-        cmd = cmd.uid(uid);
-    }
-    if let Some(gid) = config.get_gid(defaults) {
-        cmd = cmd.gid(gid);
-    }
-    if let Some(cwd) = config.get_cwd(defaults) {
-        cmd = cmd.current_dir(cwd);
-    }
     if config.get_clear_env(defaults) {
         cmd = cmd.env_clear();
     }
     if let Some(env) = config.get_env(defaults) {
         cmd = cmd.envs(env.iter());
     }
+    // uid, gid and cwd are handled by the sandbox as they must be set after
+    // entering the chroot
+    let sandbox = match Sandbox::new(&config, defaults) {
+        Ok(sandbox) => sandbox,
+        Err(e) => return CommandResult::error(config, e.into()),
+    };
+    unsafe {
+        cmd.pre_exec(move || sandbox.apply());
+    }
 
-    match cmd.spawn() {
+    let spawned = cmd.spawn();
+    drop(script);
+    match spawned {
         Ok(child) => {
-            let output = tokio::time::timeout(
+            let (result, usage) = wait_child(
+                child,
+                input,
+                config.get_max_output_bytes(defaults),
                 Duration::from_secs(config.timeout.0 as u64),
-                child.wait_with_output(),
             )
             .await;
-            match output {
-                Ok(Ok(output)) if output.status.success() => {
-                    CommandResult::ok(config, output.into())
-                }
-                Ok(Ok(output)) => CommandResult::error(config, ReturnedError { output }.into()),
-
-                Ok(Err(e)) => CommandResult::error(config, e.into()),
-                Err(e) => CommandResult::error(config, e.into()),
+            CommandResult {
+                config,
+                result: result.map(CommandOutput::from),
+                usage,
             }
         }
         Err(e) => CommandResult::error(config, e.into()),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{Namespace, ResourceLimits, Timeout};
+
+    #[tokio::test]
+    async fn test_execute_command_collects_usage() {
+        let config = CommandConfig {
+            name: "busy".to_string(),
+            run: Some("i=0; while [ $i -lt 20000 ]; do i=$((i+1)); done; echo done".into()),
+            ..Default::default()
+        };
+        let result = execute_command(config, &DefaultRunnerEnv::default()).await;
+        let output = result.result.unwrap();
+        assert_eq!(output.stdout, "done\n");
+        let usage = result.usage.unwrap();
+        assert!(usage.user_time + usage.system_time > 0);
+        assert!(usage.max_rss > 0);
+    }
+
+    #[tokio::test]
+    async fn test_execute_command_failure_collects_usage() {
+        let config = CommandConfig {
+            name: "false".to_string(),
+            run: Some("echo oops >&2; exit 3".into()),
+            ..Default::default()
+        };
+        let result = execute_command(config, &DefaultRunnerEnv::default()).await;
+        match result.result {
+            Err(CommandError::ReturnedError(e)) => {
+                assert_eq!(e.output.status.code(), Some(3));
+                assert_eq!(String::from_utf8_lossy(&e.output.stderr), "oops\n");
+            }
+            _ => panic!("unexpected result: {:?}", result.result),
+        }
+        assert!(result.usage.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_execute_command_rlimits() {
+        let config = CommandConfig {
+            name: "ulimit".to_string(),
+            run: Some("ulimit -n".into()),
+            rlimits: Some(ResourceLimits {
+                nofile: Some(42),
+                ..Default::default()
+            }),
+            no_new_privs: true,
+            cwd: Some("/".to_string()),
+            ..Default::default()
+        };
+        let result = execute_command(config, &DefaultRunnerEnv::default()).await;
+        assert_eq!(result.result.unwrap().stdout, "42\n");
+    }
+
+    #[tokio::test]
+    async fn test_execute_command_argv() {
+        let config = CommandConfig {
+            name: "argv".to_string(),
+            run: Some(Run::Argv(vec![
+                "printf".to_string(),
+                "%s|%s".to_string(),
+                "$HOME 'quoted'".to_string(),
+                "b".to_string(),
+            ])),
+            ..Default::default()
+        };
+        let result = execute_command(config, &DefaultRunnerEnv::default()).await;
+        assert_eq!(result.result.unwrap().stdout, "$HOME 'quoted'|b");
+    }
+
+    #[tokio::test]
+    async fn test_execute_command_script() {
+        let config = CommandConfig {
+            name: "script".to_string(),
+            interpreter: Some("sh".to_string()),
+            script: Some("for i in 1 2; do\n  echo \"line $i\"\ndone\n".to_string()),
+            ..Default::default()
+        };
+        let result = execute_command(config, &DefaultRunnerEnv::default()).await;
+        assert_eq!(result.result.unwrap().stdout, "line 1\nline 2\n");
+    }
+
+    #[tokio::test]
+    async fn test_execute_command_stdin() {
+        let config = CommandConfig {
+            name: "stdin".to_string(),
+            run: Some("tr a-z A-Z".into()),
+            stdin: Some("hello\n".to_string()),
+            ..Default::default()
+        };
+        let result = execute_command(config, &DefaultRunnerEnv::default()).await;
+        assert_eq!(result.result.unwrap().stdout, "HELLO\n");
+    }
+
+    #[tokio::test]
+    async fn test_execute_command_stdin_file() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        write!(file, "from file").unwrap();
+        let config = CommandConfig {
+            name: "stdin_file".to_string(),
+            run: Some("cat".into()),
+            stdin_file: Some(file.path().to_str().unwrap().to_string()),
+            ..Default::default()
+        };
+        let result = execute_command(config, &DefaultRunnerEnv::default()).await;
+        assert_eq!(result.result.unwrap().stdout, "from file");
+    }
+
+    #[tokio::test]
+    async fn test_execute_command_stdin_default_is_null() {
+        let config = CommandConfig {
+            name: "cat".to_string(),
+            run: Some("cat".into()),
+            timeout: Timeout(5),
+            ..Default::default()
+        };
+        let result = execute_command(config, &DefaultRunnerEnv::default()).await;
+        assert_eq!(result.result.unwrap().stdout, "");
+    }
+
+    #[test]
+    fn test_capture() {
+        let mut capture = Capture::new(None);
+        capture.extend(b"0123456789");
+        assert_eq!(capture.finish(), b"0123456789");
+
+        let mut capture = Capture::new(Some(10));
+        capture.extend(b"0123456789");
+        assert_eq!(capture.finish(), b"0123456789");
+
+        let mut capture = Capture::new(Some(4));
+        for _ in 0..3000 {
+            capture.extend(b"abc");
+        }
+        capture.extend(b"0123456789");
+        assert_eq!(
+            String::from_utf8(capture.finish()).unwrap(),
+            "ab\n[... 9006 bytes truncated ...]\n89"
+        );
+
+        // Characters are not cut in two
+        let mut capture = Capture::new(Some(6));
+        capture.extend("é".repeat(3000).as_bytes());
+        assert_eq!(
+            String::from_utf8(capture.finish()).unwrap(),
+            "é\n[... 5996 bytes truncated ...]\né"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_execute_command_max_output_bytes() {
+        let config = CommandConfig {
+            name: "seq".to_string(),
+            run: Some("seq 1 100000; seq 1 10 >&2".into()),
+            max_output_bytes: Some(10),
+            ..Default::default()
+        };
+        let result = execute_command(config, &DefaultRunnerEnv::default()).await;
+        let output = result.result.unwrap();
+        assert_eq!(
+            output.stdout,
+            "1\n2\n3\n[... 588885 bytes truncated ...]\n0000\n"
+        );
+        assert_eq!(
+            output.stderr,
+            "1\n2\n3\n[... 11 bytes truncated ...]\n9\n10\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_execute_command_timeout() {
+        let config = CommandConfig {
+            name: "sleep".to_string(),
+            run: Some("sleep 10".into()),
+            timeout: Timeout(1),
+            ..Default::default()
+        };
+        let result = execute_command(config, &DefaultRunnerEnv::default()).await;
+        assert!(matches!(result.result, Err(CommandError::TimedOut(_))));
+        assert!(result.usage.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_execute_command_pid_namespace() {
+        // Unsharing a PID namespace needs to be root
+        if unsafe { libc::geteuid() } != 0 {
+            return;
+        }
+        let config = CommandConfig {
+            name: "missing".to_string(),
+            run: Some(Run::Argv(vec!["ronde-missing-executable".to_string()])),
+            unshare: Some(vec![Namespace::Pid]),
+            ..Default::default()
+        };
+        let result = execute_command(config, &DefaultRunnerEnv::default()).await;
+        assert!(matches!(result.result, Err(CommandError::Command(_))));
+
+        // The CPU hard limit kills the command with SIGKILL
+        let config = CommandConfig {
+            name: "busy".to_string(),
+            run: Some("while :; do :; done".into()),
+            unshare: Some(vec![Namespace::Pid]),
+            rlimits: Some(ResourceLimits {
+                cpu: Some(1),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let result = execute_command(config, &DefaultRunnerEnv::default()).await;
+        match result.result {
+            Err(CommandError::ReturnedError(e)) => {
+                assert_eq!(e.output.status.signal(), Some(libc::SIGKILL));
+            }
+            _ => panic!("unexpected result: {:?}", result.result),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_execute_command_cancelled() {
+        let dir = tempfile::tempdir().unwrap();
+        let pid_file = dir.path().join("pid");
+        let config = CommandConfig {
+            name: "sleep".to_string(),
+            run: Some(Run::Shell(format!(
+                "echo $$ > {}; exec sleep 30",
+                pid_file.display()
+            ))),
+            timeout: Timeout(60),
+            ..Default::default()
+        };
+        let defaults = DefaultRunnerEnv::default();
+        let cancelled = tokio::time::timeout(
+            Duration::from_millis(500),
+            execute_command(config, &defaults),
+        )
+        .await;
+        assert!(cancelled.is_err());
+        let pid: libc::pid_t = std::fs::read_to_string(&pid_file)
+            .unwrap()
+            .trim()
+            .parse()
+            .unwrap();
+        // Killed and reaped in the background
+        let start = Instant::now();
+        while unsafe { libc::kill(pid, 0) } == 0 {
+            assert!(
+                start.elapsed() < Duration::from_secs(5),
+                "{pid} still running"
+            );
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    }
+}
@@ -0,0 +1,215 @@
+use crate::config::{CommandConfig, DefaultRunnerEnv, Namespace, ResourceLimits};
+use crate::user::{group_of, User, UserError};
+use std::ffi::CString;
+use std::io;
+use thiserror::Error;
+
+/// Error type for sandbox setup
+#[derive(Debug, Error)]
+pub enum SandboxError {
+    /// A path contains a NUL byte
+    #[error("Invalid path {0:?}: contains a NUL byte")]
+    InvalidPath(String),
+    /// The groups of the user cannot be looked up
+    #[error("{0}")]
+    User(#[from] UserError),
+}
+
+/// Settings applied to a command in the child process, between `fork` and
+/// `exec`
+///
+/// Everything is prepared beforehand so that `apply` does not allocate.
+#[derive(Debug, Default)]
+pub struct Sandbox {
+    /// Resource limits
+    rlimits: ResourceLimits,
+    /// Flags to give to `unshare(2)`
+    unshare_flags: libc::c_int,
+    /// Directory to chroot into
+    chroot: Option<CString>,
+    /// Directory to change to
+    cwd: Option<CString>,
+    /// UID to switch to
+    uid: Option<u32>,
+    /// GID to switch to
+    gid: Option<u32>,
+    /// Supplementary groups to set, only when running as root
+    groups: Option<Vec<libc::gid_t>>,
+    /// Set the `no_new_privs` flag
+    no_new_privs: bool,
+}
+
+fn to_cstring(path: String) -> Result<CString, SandboxError> {
+    CString::new(path.clone()).map_err(|_| SandboxError::InvalidPath(path))
+}
+
+/// Map the return value of a libc call to an `io::Result`
+fn cvt(ret: libc::c_int) -> io::Result<()> {
+    if ret == -1 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(())
+    }
+}
+
+impl Sandbox {
+    /// Create the sandbox of a command based on its config and the defaults
+    pub fn new(config: &CommandConfig, defaults: &DefaultRunnerEnv) -> Result<Self, SandboxError> {
+        let chroot = config.get_chroot(defaults).map(to_cstring).transpose()?;
+        let cwd = match config.get_cwd(defaults) {
+            Some(cwd) => Some(to_cstring(cwd)?),
+            // Do not stay outside of the chroot
+            None if chroot.is_some() => Some(to_cstring("/".to_string())?),
+            None => None,
+        };
+        let unshare_flags = config
+            .get_unshare(defaults)
+            .iter()
+            .fold(0, |flags, ns| match ns {
+                Namespace::Network => flags | libc::CLONE_NEWNET,
+                Namespace::Mount => flags | libc::CLONE_NEWNS,
+                Namespace::Pid => flags | libc::CLONE_NEWPID,
+            });
+        let uid = config.get_uid(defaults);
+        let gid = group_of(uid, config.get_gid(defaults))?;
+        let groups = if unsafe { libc::geteuid() } == 0 {
+            match (uid, gid) {
+                (Some(uid), gid) => Some(match User::from_uid(uid)? {
+                    // Like initgroups(3), which cannot be called after fork
+                    Some(user) => user.groups(gid.unwrap_or(user.gid))?,
+                    None => gid.into_iter().collect(),
+                }),
+                (None, Some(gid)) => Some(vec![gid]),
+                (None, None) => None,
+            }
+        } else {
+            None
+        };
+        Ok(Sandbox {
+            rlimits: config.get_rlimits(defaults),
+            unshare_flags,
+            chroot,
+            cwd,
+            uid,
+            gid,
+            groups,
+            no_new_privs: config.get_no_new_privs(defaults),
+        })
+    }
+
+    /// Apply the sandbox to the current process
+    ///
+    /// This is meant to be run in a `pre_exec` hook: namespaces are
+    /// unshared and the chroot is entered while still privileged, then
+    /// resource limits are set and privileges are dropped.
+    pub fn apply(&self) -> io::Result<()> {
+        if self.unshare_flags != 0 {
+            cvt(unsafe { libc::unshare(self.unshare_flags) })?;
+        }
+        if self.unshare_flags & libc::CLONE_NEWNS != 0 {
+            // Do not propagate mounts back to the host
+            cvt(unsafe {
+                libc::mount(
+                    c"none".as_ptr(),
+                    c"/".as_ptr(),
+                    std::ptr::null(),
+                    libc::MS_REC | libc::MS_PRIVATE,
+                    std::ptr::null(),
+                )
+            })?;
+        }
+        if let Some(ref chroot) = self.chroot {
+            cvt(unsafe { libc::chroot(chroot.as_ptr()) })?;
+        }
+        self.set_rlimits()?;
+        if let Some(ref groups) = self.groups {
+            cvt(unsafe { libc::setgroups(groups.len(), groups.as_ptr()) })?;
+        }
+        if let Some(gid) = self.gid {
+            cvt(unsafe { libc::setgid(gid) })?;
+        }
+        if let Some(uid) = self.uid {
+            cvt(unsafe { libc::setuid(uid) })?;
+        }
+        if let Some(ref cwd) = self.cwd {
+            cvt(unsafe { libc::chdir(cwd.as_ptr()) })?;
+        }
+        if self.no_new_privs {
+            cvt(unsafe { libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) })?;
+        }
+        if self.unshare_flags & libc::CLONE_NEWPID != 0 {
+            fork_into_pid_namespace()?;
+        }
+        Ok(())
+    }
+
+    fn set_rlimits(&self) -> io::Result<()> {
+        let limits = [
+            (libc::RLIMIT_AS, self.rlimits.address_space),
+            (libc::RLIMIT_NPROC, self.rlimits.nproc),
+            (libc::RLIMIT_NOFILE, self.rlimits.nofile),
+            (libc::RLIMIT_CPU, self.rlimits.cpu),
+        ];
+        for (resource, value) in limits {
+            if let Some(value) = value {
+                let rlimit = libc::rlimit {
+                    rlim_cur: value as libc::rlim_t,
+                    rlim_max: value as libc::rlim_t,
+                };
+                cvt(unsafe { libc::setrlimit(resource, &rlimit) })?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Fork so that the command becomes the init process of the new PID
+/// namespace
+///
+/// `unshare(CLONE_NEWPID)` only moves the future children of the caller into
+/// the new namespace. The calling process stays outside of it, waits for the
+/// command and ends the same way, with the same exit code or signal, so
+/// that ronde can still wait for it and kill it.
+fn fork_into_pid_namespace() -> io::Result<()> {
+    match unsafe { libc::fork() } {
+        -1 => Err(io::Error::last_os_error()),
+        0 => {
+            // Die along with the intermediate process, e.g. on timeout.
+            // Killing the init process kills the whole namespace.
+            cvt(unsafe { libc::prctl(libc::PR_SET_PDEATHSIG, libc::SIGKILL) })
+        }
+        pid => {
+            // Only keep the standard streams so that the spawning side does
+            // not wait on file descriptors held by this process. The command
+            // keeps its own copy of the pipe std uses to report exec errors,
+            // which still reach the spawning side.
+            let ret = unsafe { libc::syscall(libc::SYS_close_range, 3, libc::c_uint::MAX, 0) };
+            if ret == -1 {
+                for fd in 3..1024 {
+                    unsafe { libc::close(fd) };
+                }
+            }
+            let mut status: libc::c_int = 0;
+            while unsafe { libc::waitpid(pid, &mut status, 0) } == -1 {
+                if io::Error::last_os_error().kind() != io::ErrorKind::Interrupted {
+                    unsafe { libc::_exit(127) };
+                }
+            }
+            if libc::WIFSIGNALED(status) {
+                // Die of the same signal, without dumping core twice
+                let signal = libc::WTERMSIG(status);
+                unsafe {
+                    libc::prctl(libc::PR_SET_DUMPABLE, 0);
+                    libc::signal(signal, libc::SIG_DFL);
+                    let mut set: libc::sigset_t = std::mem::zeroed();
+                    libc::sigemptyset(&mut set);
+                    libc::sigaddset(&mut set, signal);
+                    libc::sigprocmask(libc::SIG_UNBLOCK, &set, std::ptr::null_mut());
+                    libc::kill(libc::getpid(), signal);
+                    libc::_exit(128 + signal)
+                }
+            }
+            unsafe { libc::_exit(libc::WEXITSTATUS(status)) }
+        }
+    }
+}
@@ -0,0 +1,197 @@
+use std::collections::HashMap;
+use thiserror::Error;
+
+/// Replacement for secret values
+const REDACTED: &str = "********";
+
+/// Secret values shorter than this are not redacted, as they would garble
+/// every output they appear in
+const MIN_SECRET_LEN: usize = 4;
+
+/// Error type for secrets
+#[derive(Debug, Error)]
+pub enum SecretError {
+    /// Environment variable referenced by `${NAME}` is not set
+    #[error("Environment variable {0} is not set")]
+    UndefinedVariable(String),
+    /// `${` without a closing `}`
+    #[error("Unterminated variable reference in {0:?}")]
+    UnterminatedVariable(String),
+    /// Line of an env file that is not `KEY=VALUE`
+    #[error("Invalid line {line} in env file {path}")]
+    InvalidEnvFile { path: String, line: usize },
+}
+
+/// Secret values, which must not appear in anything ronde echoes
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Secrets(Vec<String>);
+
+impl Secrets {
+    /// Record a secret value
+    pub fn add(&mut self, value: &str) {
+        if value.len() >= MIN_SECRET_LEN && !self.0.iter().any(|v| v == value) {
+            self.0.push(value.to_string());
+            // Longest first so that a secret containing another one is
+            // fully redacted
+            self.0.sort_by_key(|v| std::cmp::Reverse(v.len()));
+        }
+    }
+
+    /// Whether there are no secrets
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Replace every secret value found in `text`
+    pub fn redact(&self, text: &str) -> String {
+        let mut text = text.to_string();
+        for secret in self.0.iter() {
+            if text.contains(secret.as_str()) {
+                text = text.replace(secret.as_str(), REDACTED);
+            }
+        }
+        text
+    }
+}
+
+/// Replace `${NAME}` with the value of the environment variable `NAME`
+///
+/// In values containing `${`, `$$` is replaced by a single `$`; other values
+/// are kept as they are. Returns whether a variable was substituted, in which
+/// case the value is to be considered secret.
+pub fn interpolate(value: &str) -> Result<(String, bool), SecretError> {
+    if !value.contains("${") {
+        return Ok((value.to_string(), false));
+    }
+    let mut result = String::with_capacity(value.len());
+    let mut substituted = false;
+    let mut rest = value;
+    while let Some(pos) = rest.find('$') {
+        result.push_str(&rest[..pos]);
+        rest = &rest[pos..];
+        if let Some(after) = rest.strip_prefix("$$") {
+            result.push('$');
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix("${") {
+            let end = after
+                .find('}')
+                .ok_or_else(|| SecretError::UnterminatedVariable(value.to_string()))?;
+            let name = &after[..end];
+            let var = std::env::var(name)
+                .map_err(|_| SecretError::UndefinedVariable(name.to_string()))?;
+            result.push_str(&var);
+            substituted = true;
+            rest = &after[end + 1..];
+        } else {
+            result.push('$');
+            rest = &rest[1..];
+        }
+    }
+    result.push_str(rest);
+    Ok((result, substituted))
+}
+
+/// Parse the content of a dotenv file
+///
+/// Each line is `KEY=VALUE`, optionally prefixed with `export `. Values can
+/// be surrounded by single or double quotes. Empty lines and lines starting
+/// with `#` are ignored.
+pub fn parse_env_file(path: &str, content: &str) -> Result<HashMap<String, String>, SecretError> {
+    let mut env = HashMap::new();
+    for (idx, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line.strip_prefix("export ").unwrap_or(line);
+        let (key, value) = line
+            .split_once('=')
+            .ok_or_else(|| SecretError::InvalidEnvFile {
+                path: path.to_string(),
+                line: idx + 1,
+            })?;
+        let key = key.trim();
+        if key.is_empty() {
+            return Err(SecretError::InvalidEnvFile {
+                path: path.to_string(),
+                line: idx + 1,
+            });
+        }
+        let value = value.trim();
+        let value = match (value.chars().next(), value.chars().last()) {
+            (Some(first @ ('"' | '\'')), Some(last)) if value.len() >= 2 && first == last => {
+                &value[1..value.len() - 1]
+            }
+            _ => value,
+        };
+        env.insert(key.to_string(), value.to_string());
+    }
+    Ok(env)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_interpolate() {
+        std::env::set_var("RONDE_TEST_INTERPOLATE", "s3cr3t");
+        assert_eq!(
+            interpolate("plain $HOME").unwrap(),
+            ("plain $HOME".to_string(), false)
+        );
+        assert_eq!(
+            interpolate("echo $$ costs $$5").unwrap(),
+            ("echo $$ costs $$5".to_string(), false)
+        );
+        assert_eq!(
+            interpolate("$$5 for ${RONDE_TEST_INTERPOLATE}").unwrap(),
+            ("$5 for s3cr3t".to_string(), true)
+        );
+        assert_eq!(
+            interpolate("pass=${RONDE_TEST_INTERPOLATE}!").unwrap(),
+            ("pass=s3cr3t!".to_string(), true)
+        );
+        assert_eq!(
+            interpolate("$${RONDE_TEST_INTERPOLATE}").unwrap(),
+            ("${RONDE_TEST_INTERPOLATE}".to_string(), false)
+        );
+        assert!(interpolate("${RONDE_TEST_UNDEFINED}").is_err());
+        assert!(interpolate("${RONDE_TEST_INTERPOLATE").is_err());
+    }
+
+    #[test]
+    fn test_parse_env_file() {
+        let env = parse_env_file(
+            "test.env",
+            r#"
+# comment
+export DB_USER=ronde
+DB_PASSWORD = "pass word"
+TOKEN='abc=def'
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            env,
+            HashMap::from([
+                ("DB_USER".to_string(), "ronde".to_string()),
+                ("DB_PASSWORD".to_string(), "pass word".to_string()),
+                ("TOKEN".to_string(), "abc=def".to_string()),
+            ])
+        );
+        assert!(parse_env_file("test.env", "NOT A VARIABLE").is_err());
+    }
+
+    #[test]
+    fn test_redact() {
+        let mut secrets = Secrets::default();
+        secrets.add("s3cr3t");
+        secrets.add("s3cr3t-longer");
+        secrets.add("1");
+        assert_eq!(
+            secrets.redact("curl -u s3cr3t-longer https://1.example/s3cr3t"),
+            "curl -u ******** https://1.example/********"
+        );
+    }
+}
@@ -0,0 +1,257 @@
+use crate::config::shell_quote;
+use std::collections::HashMap;
+use thiserror::Error;
+use toml::{Table, Value};
+
+/// Error type for templates
+#[derive(Debug, Error)]
+pub enum TemplateError {
+    /// Template referenced by a command does not exist
+    #[error("Unknown template {0}")]
+    UnknownTemplate(String),
+    /// Template inheriting from itself
+    #[error("Template {0} inherits from itself")]
+    RecursiveTemplate(String),
+    /// `template` is not a string
+    #[error("template must be a string")]
+    InvalidTemplateName,
+    /// `vars` is not a table
+    #[error("vars must be a table")]
+    InvalidVars,
+    /// Variable used but not defined
+    #[error("Variable {0} is not defined")]
+    UndefinedVariable(String),
+}
+
+/// Merge `overrides` into `base`
+///
+/// Tables are merged recursively, any other value of `overrides` replaces
+/// the one of `base`.
+pub fn merge(base: &mut Table, overrides: Table) {
+    for (key, value) in overrides {
+        match (base.get_mut(&key), value) {
+            (Some(Value::Table(base_table)), Value::Table(table)) => merge(base_table, table),
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+/// Whether `name` can be used as a variable name
+fn is_variable_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Replace `{{name}}` with the value of the variable `name`
+///
+/// Anything between double braces that is not a variable name, like
+/// `{{.State.Running}}` for `docker inspect`, is left untouched.
+pub fn substitute(text: &str, vars: &HashMap<String, String>) -> Result<String, TemplateError> {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("{{") {
+        let Some(len) = rest[start + 2..].find("}}") else {
+            break;
+        };
+        let name = rest[start + 2..start + 2 + len].trim();
+        result.push_str(&rest[..start]);
+        if is_variable_name(name) {
+            let value = vars
+                .get(name)
+                .ok_or_else(|| TemplateError::UndefinedVariable(name.to_string()))?;
+            result.push_str(value);
+        } else {
+            result.push_str(&rest[start..start + 2 + len + 2]);
+        }
+        rest = &rest[start + 2 + len + 2..];
+    }
+    result.push_str(rest);
+    Ok(result)
+}
+
+/// Substitute the variables in every string of `value`
+fn substitute_value(
+    value: &mut Value,
+    vars: &HashMap<String, String>,
+) -> Result<(), TemplateError> {
+    match value {
+        Value::String(s) => *s = substitute(s, vars)?,
+        Value::Array(array) => {
+            for v in array.iter_mut() {
+                substitute_value(v, vars)?;
+            }
+        }
+        Value::Table(table) => {
+            for (_, v) in table.iter_mut() {
+                substitute_value(v, vars)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Resolve the template of a command
+///
+/// The command is merged over its template, which can itself inherit from
+/// another template. Then the variables defined in `vars` are substituted
+/// in every string. Commands using neither a template nor variables are
+/// left untouched.
+pub fn resolve(command: Table, templates: &HashMap<String, Table>) -> Result<Table, TemplateError> {
+    let mut resolved = command;
+    let mut seen: Vec<String> = Vec::new();
+    while let Some(name) = resolved.remove("template") {
+        let name = name
+            .as_str()
+            .ok_or(TemplateError::InvalidTemplateName)?
+            .to_string();
+        if seen.contains(&name) {
+            return Err(TemplateError::RecursiveTemplate(name));
+        }
+        let mut base = templates
+            .get(&name)
+            .ok_or_else(|| TemplateError::UnknownTemplate(name.clone()))?
+            .clone();
+        merge(&mut base, resolved);
+        resolved = base;
+        seen.push(name);
+    }
+    let vars = match resolved.remove("vars") {
+        None if seen.is_empty() => return Ok(resolved),
+        None => Table::new(),
+        Some(Value::Table(vars)) => vars,
+        Some(_) => return Err(TemplateError::InvalidVars),
+    };
+    let vars: HashMap<String, String> = vars
+        .into_iter()
+        .map(|(k, v)| match v {
+            Value::String(s) => (k, s),
+            v => (k, v.to_string()),
+        })
+        .collect();
+    // A command line run through a shell gets quoted values, so that they
+    // cannot inject commands
+    let quoted: HashMap<String, String> = vars
+        .iter()
+        .map(|(k, v)| (k.clone(), shell_quote(v)))
+        .collect();
+    for (key, value) in resolved.iter_mut() {
+        match (key.as_str(), value) {
+            ("run", Value::String(command_line)) => {
+                *command_line = substitute(command_line, &quoted)?
+            }
+            (_, value) => substitute_value(value, &vars)?,
+        }
+    }
+    Ok(resolved)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_substitute() {
+        let vars = HashMap::from([("host".to_string(), "example.com".to_string())]);
+        assert_eq!(
+            substitute("ping -c 4 {{host}} {{ host }}", &vars).unwrap(),
+            "ping -c 4 example.com example.com"
+        );
+        assert_eq!(
+            substitute("docker inspect -f '{{.State.Running}}' {{host}}", &vars).unwrap(),
+            "docker inspect -f '{{.State.Running}}' example.com"
+        );
+        assert_eq!(substitute("{{host", &vars).unwrap(), "{{host");
+        assert!(substitute("{{port}}", &vars).is_err());
+    }
+
+    #[test]
+    fn test_shell_quote() {
+        assert_eq!(
+            shell_quote("web-1.example.com:8080"),
+            "web-1.example.com:8080"
+        );
+        assert_eq!(shell_quote(""), "''");
+        assert_eq!(shell_quote("a b"), "'a b'");
+        assert_eq!(shell_quote("it's"), "'it'\\''s'");
+        assert_eq!(shell_quote("$(reboot)"), "'$(reboot)'");
+    }
+
+    #[test]
+    fn test_resolve() {
+        let templates: HashMap<String, Table> = toml::from_str(
+            r#"
+[base]
+    timeout = 5
+    tags = ["network"]
+    env.LANG = "C"
+[ping]
+    template = "base"
+    name = "ping {{host}}"
+    run = "ping -c {{count}} {{host}}"
+    vars.count = 4
+[loop]
+    template = "loop"
+"#,
+        )
+        .unwrap();
+        let command: Table = toml::from_str(
+            r#"
+template = "ping"
+timeout = 10
+env.TZ = "UTC"
+vars.host = "example.com"
+"#,
+        )
+        .unwrap();
+        let expected: Table = toml::from_str(
+            r#"
+name = "ping example.com"
+run = "ping -c 4 example.com"
+timeout = 10
+tags = ["network"]
+env.LANG = "C"
+env.TZ = "UTC"
+"#,
+        )
+        .unwrap();
+        assert_eq!(resolve(command, &templates).unwrap(), expected);
+
+        let command: Table = toml::from_str("template = \"loop\"").unwrap();
+        assert!(matches!(
+            resolve(command, &templates),
+            Err(TemplateError::RecursiveTemplate(_))
+        ));
+        let command: Table = toml::from_str("template = \"unknown\"").unwrap();
+        assert!(matches!(
+            resolve(command, &templates),
+            Err(TemplateError::UnknownTemplate(_))
+        ));
+
+        // Values are quoted in shell command lines only
+        let command: Table = toml::from_str(
+            r#"
+name = "ping {{host}}"
+run = "ping -c 4 {{host}}"
+vars.host = "example.com; rm -rf ~"
+"#,
+        )
+        .unwrap();
+        let resolved = resolve(command, &templates).unwrap();
+        assert_eq!(
+            resolved["name"].as_str(),
+            Some("ping example.com; rm -rf ~")
+        );
+        assert_eq!(
+            resolved["run"].as_str(),
+            Some("ping -c 4 'example.com; rm -rf ~'")
+        );
+
+        // Without template nor vars, braces are left alone
+        let command: Table = toml::from_str("run = \"echo {{host}}\"").unwrap();
+        assert_eq!(resolve(command.clone(), &templates).unwrap(), command);
+    }
+}
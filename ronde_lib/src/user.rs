@@ -0,0 +1,275 @@
+use std::ffi::{CStr, CString};
+use std::io;
+use thiserror::Error;
+
+/// Error type for users and groups
+#[derive(Debug, Error)]
+pub enum UserError {
+    /// No user with this name
+    #[error("Unknown user {0}")]
+    UnknownUser(String),
+    /// No group with this name
+    #[error("Unknown group {0}")]
+    UnknownGroup(String),
+    /// The user or group database could not be read
+    #[error("Unable to look up {name}: {source}")]
+    Lookup { name: String, source: io::Error },
+    /// No group to switch to along with a UID unknown to the user database
+    #[error("No gid set and no user with uid {0} to take its group from")]
+    NoPrimaryGroup(u32),
+    /// Failed to set the supplementary groups
+    #[error("Unable to set the supplementary groups: {0}")]
+    SetGroups(io::Error),
+    /// Failed to change the group
+    #[error("Unable to setgid to {gid}: {source}")]
+    SetGid { gid: u32, source: io::Error },
+    /// Failed to change the user
+    #[error("Unable to setuid to {uid}: {source}")]
+    SetUid { uid: u32, source: io::Error },
+}
+
+/// Entry of the user database
+#[derive(Debug, Clone, PartialEq)]
+pub struct User {
+    /// Login name
+    pub name: String,
+    /// UID
+    pub uid: u32,
+    /// Primary GID
+    pub gid: u32,
+}
+
+/// Initial size of the buffers given to the `get*_r` functions
+const BUFFER_SIZE: usize = 1024;
+
+/// Call one of the reentrant `getpw*_r`/`getgr*_r` functions, growing the
+/// buffer as long as it is too small
+///
+/// Returns `Ok(false)` when there is no matching entry.
+fn lookup<F>(name: &str, mut call: F) -> Result<bool, UserError>
+where
+    F: FnMut(&mut [libc::c_char]) -> (libc::c_int, bool),
+{
+    let mut buf = vec![0 as libc::c_char; BUFFER_SIZE];
+    loop {
+        match call(&mut buf) {
+            (0, found) => return Ok(found),
+            (libc::ERANGE, _) => buf.resize(buf.len() * 2, 0),
+            // Not found, according to getpwnam_r(3)
+            (libc::ENOENT | libc::ESRCH | libc::EBADF | libc::EPERM, _) => return Ok(false),
+            (errno, _) => {
+                return Err(UserError::Lookup {
+                    name: name.to_string(),
+                    source: io::Error::from_raw_os_error(errno),
+                })
+            }
+        }
+    }
+}
+
+impl User {
+    fn from_passwd(pwd: &libc::passwd) -> Self {
+        User {
+            name: unsafe { CStr::from_ptr(pwd.pw_name) }
+                .to_string_lossy()
+                .to_string(),
+            uid: pwd.pw_uid,
+            gid: pwd.pw_gid,
+        }
+    }
+
+    /// Look a user up by name
+    pub fn from_name(name: &str) -> Result<Self, UserError> {
+        let Ok(c_name) = CString::new(name) else {
+            return Err(UserError::UnknownUser(name.to_string()));
+        };
+        let mut pwd: libc::passwd = unsafe { std::mem::zeroed() };
+        let mut user = None;
+        lookup(name, |buf| {
+            let mut result = std::ptr::null_mut();
+            let ret = unsafe {
+                libc::getpwnam_r(
+                    c_name.as_ptr(),
+                    &mut pwd,
+                    buf.as_mut_ptr(),
+                    buf.len(),
+                    &mut result,
+                )
+            };
+            if ret == 0 && !result.is_null() {
+                user = Some(User::from_passwd(&pwd));
+            }
+            (ret, !result.is_null())
+        })?;
+        user.ok_or_else(|| UserError::UnknownUser(name.to_string()))
+    }
+
+    /// Look a user up by UID, returns `None` if there is no such user
+    pub fn from_uid(uid: u32) -> Result<Option<Self>, UserError> {
+        let mut pwd: libc::passwd = unsafe { std::mem::zeroed() };
+        let mut user = None;
+        lookup(&uid.to_string(), |buf| {
+            let mut result = std::ptr::null_mut();
+            let ret = unsafe {
+                libc::getpwuid_r(uid, &mut pwd, buf.as_mut_ptr(), buf.len(), &mut result)
+            };
+            if ret == 0 && !result.is_null() {
+                user = Some(User::from_passwd(&pwd));
+            }
+            (ret, !result.is_null())
+        })?;
+        Ok(user)
+    }
+
+    /// Get the groups of the user, as `initgroups(3)` would set them, with
+    /// `gid` as the primary group
+    pub fn groups(&self, gid: u32) -> Result<Vec<libc::gid_t>, UserError> {
+        let Ok(c_name) = CString::new(self.name.as_str()) else {
+            return Ok(vec![gid]);
+        };
+        let mut groups: Vec<libc::gid_t> = vec![0; 32];
+        loop {
+            let mut len = groups.len() as libc::c_int;
+            let ret =
+                unsafe { libc::getgrouplist(c_name.as_ptr(), gid, groups.as_mut_ptr(), &mut len) };
+            if ret != -1 {
+                groups.truncate(len as usize);
+                return Ok(groups);
+            }
+            // `len` holds the number of groups needed
+            let needed = (len as usize).max(groups.len() * 2);
+            groups.resize(needed, 0);
+        }
+    }
+}
+
+/// Look a group up by name, returns its GID
+pub fn group_from_name(name: &str) -> Result<u32, UserError> {
+    let Ok(c_name) = CString::new(name) else {
+        return Err(UserError::UnknownGroup(name.to_string()));
+    };
+    let mut grp: libc::group = unsafe { std::mem::zeroed() };
+    let found = lookup(name, |buf| {
+        let mut result = std::ptr::null_mut();
+        let ret = unsafe {
+            libc::getgrnam_r(
+                c_name.as_ptr(),
+                &mut grp,
+                buf.as_mut_ptr(),
+                buf.len(),
+                &mut result,
+            )
+        };
+        (ret, !result.is_null())
+    })?;
+    if found {
+        Ok(grp.gr_gid)
+    } else {
+        Err(UserError::UnknownGroup(name.to_string()))
+    }
+}
+
+/// Whether a group with this GID exists
+pub fn group_exists(gid: u32) -> bool {
+    let mut grp: libc::group = unsafe { std::mem::zeroed() };
+    lookup(&gid.to_string(), |buf| {
+        let mut result = std::ptr::null_mut();
+        let ret =
+            unsafe { libc::getgrgid_r(gid, &mut grp, buf.as_mut_ptr(), buf.len(), &mut result) };
+        (ret, !result.is_null())
+    })
+    .unwrap_or(false)
+}
+
+/// Get the group to switch to along with `uid`: `gid` if set, else the
+/// primary group of the user
+///
+/// Otherwise a process switching from root to `uid` would keep the group of
+/// root.
+pub fn group_of(uid: Option<u32>, gid: Option<u32>) -> Result<Option<u32>, UserError> {
+    match (uid, gid) {
+        (Some(uid), None) => match User::from_uid(uid)? {
+            Some(user) => Ok(Some(user.gid)),
+            None => Err(UserError::NoPrimaryGroup(uid)),
+        },
+        (_, gid) => Ok(gid),
+    }
+}
+
+/// Switch the current process to `uid` and `gid` for good
+///
+/// Without `gid`, the primary group of the user is used. When running as
+/// root, the supplementary groups are cleared first, only keeping the group,
+/// so that no root group survives the switch.
+pub fn drop_privileges(uid: Option<u32>, gid: Option<u32>) -> Result<(), UserError> {
+    if uid.is_none() && gid.is_none() {
+        return Ok(());
+    }
+    let gid = group_of(uid, gid)?;
+    if unsafe { libc::geteuid() } == 0 {
+        let groups: Vec<libc::gid_t> = gid.into_iter().collect();
+        if unsafe { libc::setgroups(groups.len(), groups.as_ptr()) } == -1 {
+            return Err(UserError::SetGroups(io::Error::last_os_error()));
+        }
+    }
+    if let Some(gid) = gid {
+        if unsafe { libc::setgid(gid) } == -1 {
+            return Err(UserError::SetGid {
+                gid,
+                source: io::Error::last_os_error(),
+            });
+        }
+    }
+    if let Some(uid) = uid {
+        if unsafe { libc::setuid(uid) } == -1 {
+            return Err(UserError::SetUid {
+                uid,
+                source: io::Error::last_os_error(),
+            });
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lookup() {
+        let root = User::from_name("root").unwrap();
+        assert_eq!(root.uid, 0);
+        assert_eq!(User::from_uid(0).unwrap(), Some(root.clone()));
+        assert!(root.groups(root.gid).unwrap().contains(&root.gid));
+        assert!(matches!(
+            User::from_name("no such user"),
+            Err(UserError::UnknownUser(_))
+        ));
+        assert_eq!(User::from_uid(u32::MAX - 1).unwrap(), None);
+
+        let gid = group_from_name("root").unwrap();
+        assert_eq!(gid, 0);
+        assert!(group_exists(gid));
+        assert!(matches!(
+            group_from_name("no such group"),
+            Err(UserError::UnknownGroup(_))
+        ));
+    }
+
+    #[test]
+    fn test_group_of() {
+        assert_eq!(group_of(Some(0), None).unwrap(), Some(0));
+        assert_eq!(group_of(Some(0), Some(42)).unwrap(), Some(42));
+        assert_eq!(group_of(None, Some(42)).unwrap(), Some(42));
+        assert_eq!(group_of(None, None).unwrap(), None);
+        assert!(matches!(
+            group_of(Some(u32::MAX - 1), None),
+            Err(UserError::NoPrimaryGroup(_))
+        ));
+    }
+
+    #[test]
+    fn test_drop_nothing() {
+        assert!(drop_privileges(None, None).is_ok());
+    }
+}